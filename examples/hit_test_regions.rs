@@ -0,0 +1,58 @@
+//! This example shows how to build custom window chrome for a window without
+//! decorations.
+//!
+//! The `Sdl2HitTestRegions` component declares a draggable title bar and resize
+//! borders. They are handled by SDL when the window is clicked, no mouse
+//! handling is needed in the app.
+use bevy::{prelude::*, winit::WinitPlugin};
+use bevy_window_sdl2_backend::{
+    Sdl2WindowBackendPlugin,
+    hit_test::{HitTestArea, HitTestRegion, Sdl2HitTestRegions},
+};
+
+const TITLE_BAR_HEIGHT: f32 = 32.0;
+
+fn main() {
+    App::new()
+        .add_plugins((
+            Sdl2WindowBackendPlugin,
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "SDL2 hit test regions".into(),
+                        decorations: false,
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, window: Single<Entity, With<Window>>) {
+    commands.entity(*window).insert(Sdl2HitTestRegions {
+        resize_border: Some(6.0),
+        regions: vec![HitTestRegion {
+            rect: Rect::new(0.0, 0.0, f32::INFINITY, TITLE_BAR_HEIGHT),
+            area: HitTestArea::Draggable,
+        }],
+    });
+
+    commands.spawn(Camera2d);
+
+    // Title bar
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Px(TITLE_BAR_HEIGHT),
+                align_items: AlignItems::Center,
+                padding: UiRect::horizontal(Val::Px(8.0)),
+                ..default()
+            },
+            BackgroundColor(Color::srgb(0.15, 0.15, 0.2)),
+        ))
+        .with_child(Text::new("Drag me, or resize the window from its edges"));
+}
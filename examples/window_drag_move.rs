@@ -10,6 +10,10 @@
 //!
 //! The `start_drag_resize()` function behaves similarly but permits a window to
 //! be resized.
+//!
+//! SDL can only start a drag when a click begins, so with this backend the
+//! request is armed and the drag starts on the next left click, as long as the
+//! mouse doesn't move with no button held in between.
 use bevy::{math::CompassOctant, prelude::*, winit::WinitPlugin};
 use bevy_window_sdl2_backend::Sdl2WindowBackendPlugin;
 
@@ -0,0 +1,57 @@
+//! This example shows how to set the icon of a window.
+//!
+//! The icon can be an image asset or raw RGBA pixels. Image icons are applied
+//! as soon as the image is loaded.
+use bevy::{prelude::*, winit::WinitPlugin};
+use bevy_window_sdl2_backend::{Sdl2WindowBackendPlugin, window_icon::Sdl2WindowIcon};
+
+fn main() {
+    App::new()
+        .add_plugins((
+            Sdl2WindowBackendPlugin,
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: Some(Window {
+                        title: "SDL2 window icon".into(),
+                        ..default()
+                    }),
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, generate_icon)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    window: Single<Entity, With<Window>>,
+) {
+    commands.entity(*window).insert(Sdl2WindowIcon::Image(
+        asset_server.load("branding/icon.png"),
+    ));
+
+    commands.spawn(Camera2d);
+    commands.spawn(Text::new("Press space to switch to a generated icon"));
+}
+
+/// Replaces the icon with a gradient built from raw RGBA pixels.
+fn generate_icon(input: Res<ButtonInput<KeyCode>>, mut icon: Single<&mut Sdl2WindowIcon>) {
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+    let size = 32;
+    let pixels = (0..size * size)
+        .flat_map(|i| {
+            let (x, y) = (i % size, i / size);
+            [(x * 8) as u8, (y * 8) as u8, 255, 255]
+        })
+        .collect();
+    **icon = Sdl2WindowIcon::Rgba {
+        width: size,
+        height: size,
+        pixels,
+    };
+}
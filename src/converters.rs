@@ -1,11 +1,10 @@
-pub fn convert_sdl_scancode(
-    scancode: sdl2::keyboard::Scancode,
-) -> Option<bevy_input::keyboard::KeyCode> {
-    use bevy_input::keyboard::KeyCode as BevyKeyCode;
+/// Converts an SDL scancode to the [`KeyCode`](bevy_input::keyboard::KeyCode) of the key at the
+/// same position, scancodes without one are unidentified.
+pub fn convert_sdl_scancode(scancode: sdl2::keyboard::Scancode) -> bevy_input::keyboard::KeyCode {
+    use bevy_input::keyboard::{KeyCode as BevyKeyCode, NativeKeyCode};
     use sdl2::keyboard::Scancode as SdlScancode;
 
-    #[allow(unreachable_patterns)]
-    let bevy_key = match scancode {
+    match scancode {
         // Letter keys
         SdlScancode::A => BevyKeyCode::KeyA,
         SdlScancode::B => BevyKeyCode::KeyB,
@@ -192,11 +191,30 @@ pub fn convert_sdl_scancode(
         SdlScancode::KpMemClear => BevyKeyCode::NumpadMemoryClear,
         SdlScancode::KpMemAdd => BevyKeyCode::NumpadMemoryAdd,
         SdlScancode::KpMemSubtract => BevyKeyCode::NumpadMemorySubtract,
-        SdlScancode::Num => BevyKeyCode::NumLock,
+        SdlScancode::NumLockClear => BevyKeyCode::NumLock,
+        SdlScancode::KpPeriod => BevyKeyCode::NumpadDecimal,
+        SdlScancode::KpHash => BevyKeyCode::NumpadHash,
+
+        // International keys
+        SdlScancode::NonUsHash => BevyKeyCode::Backslash,
+        SdlScancode::International1 => BevyKeyCode::IntlRo,
+        SdlScancode::International2 => BevyKeyCode::KanaMode,
+        SdlScancode::International3 => BevyKeyCode::IntlYen,
+        SdlScancode::International4 => BevyKeyCode::Convert,
+        SdlScancode::International5 => BevyKeyCode::NonConvert,
+
+        // Other keys
+        SdlScancode::Menu => BevyKeyCode::ContextMenu,
+        SdlScancode::Power => BevyKeyCode::Power,
+        SdlScancode::Stop => BevyKeyCode::BrowserStop,
+        SdlScancode::Again => BevyKeyCode::Again,
+        SdlScancode::Select => BevyKeyCode::Select,
+        SdlScancode::Cancel => BevyKeyCode::Abort,
+        SdlScancode::Calculator => BevyKeyCode::LaunchApp2,
+        SdlScancode::Computer => BevyKeyCode::LaunchApp1,
 
-        // Unimplemented scancodes
-        SdlScancode::NonUsHash
-        | SdlScancode::Kp00
+        // Keys without a matching key code, or whose key code already belongs to another key
+        SdlScancode::Kp00
         | SdlScancode::Kp000
         | SdlScancode::KpLeftBrace
         | SdlScancode::KpRightBrace
@@ -224,20 +242,11 @@ pub fn convert_sdl_scancode(
         | SdlScancode::KpBinary
         | SdlScancode::KpOctal
         | SdlScancode::KpHexadecimal
-        | SdlScancode::KpTab
-        | SdlScancode::KpHash
-        | SdlScancode::KpSpace
-        | SdlScancode::KpPeriod
         | SdlScancode::AltErase
         | SdlScancode::ThousandsSeparator
         | SdlScancode::DecimalSeparator
         | SdlScancode::CurrencyUnit
         | SdlScancode::CurrencySubUnit
-        | SdlScancode::International1
-        | SdlScancode::International2
-        | SdlScancode::International3
-        | SdlScancode::International4
-        | SdlScancode::International5
         | SdlScancode::International6
         | SdlScancode::International7
         | SdlScancode::International8
@@ -246,11 +255,7 @@ pub fn convert_sdl_scancode(
         | SdlScancode::Lang7
         | SdlScancode::Lang8
         | SdlScancode::Lang9
-        | SdlScancode::SysReq
-        | SdlScancode::Cancel
-        | SdlScancode::Clear
         | SdlScancode::Prior
-        | SdlScancode::Return2
         | SdlScancode::Separator
         | SdlScancode::Out
         | SdlScancode::Oper
@@ -258,74 +263,54 @@ pub fn convert_sdl_scancode(
         | SdlScancode::CrSel
         | SdlScancode::ExSel
         | SdlScancode::Execute
-        | SdlScancode::Again
-        | SdlScancode::Select
         | SdlScancode::BrightnessDown
         | SdlScancode::BrightnessUp
         | SdlScancode::DisplaySwitch
         | SdlScancode::KbdIllumToggle
         | SdlScancode::KbdIllumDown
         | SdlScancode::KbdIllumUp
+        | SdlScancode::Www
+        | SdlScancode::KpTab
+        | SdlScancode::KpSpace
+        | SdlScancode::SysReq
+        | SdlScancode::Clear
+        | SdlScancode::Return2
         | SdlScancode::App1
         | SdlScancode::App2
-        | SdlScancode::Calculator
-        | SdlScancode::Computer
-        | SdlScancode::Stop
-        | SdlScancode::Www
-        | SdlScancode::Menu
-        | SdlScancode::NumLockClear
-        | SdlScancode::Power
-        | SdlScancode::Mode => return None,
-    };
-    Some(bevy_key)
+        | SdlScancode::Mode
+        | SdlScancode::Num => BevyKeyCode::Unidentified(NativeKeyCode::Unidentified),
+    }
 }
 
-pub fn convert_sdl_keycode(keycode: sdl2::keyboard::Keycode) -> Option<bevy_input::keyboard::Key> {
-    use bevy_input::keyboard::Key as BevyKey;
-    use sdl2::keyboard::Keycode as SdlKeycode;
+/// Converts the scancode and keycode of an SDL key event, keys SDL doesn't know are unidentified.
+pub fn convert_sdl_key(
+    scancode: Option<sdl2::keyboard::Scancode>,
+    keycode: Option<sdl2::keyboard::Keycode>,
+) -> (bevy_input::keyboard::KeyCode, bevy_input::keyboard::Key) {
+    use bevy_input::keyboard::{Key, KeyCode, NativeKey, NativeKeyCode};
+    (
+        scancode.map_or(
+            KeyCode::Unidentified(NativeKeyCode::Unidentified),
+            convert_sdl_scancode,
+        ),
+        keycode.map_or(
+            Key::Unidentified(NativeKey::Unidentified),
+            convert_sdl_keycode,
+        ),
+    )
+}
 
-    #[allow(unreachable_patterns)]
-    Some(match keycode {
-        // Letter keys
-        SdlKeycode::A
-        | SdlKeycode::B
-        | SdlKeycode::C
-        | SdlKeycode::D
-        | SdlKeycode::E
-        | SdlKeycode::F
-        | SdlKeycode::G
-        | SdlKeycode::H
-        | SdlKeycode::I
-        | SdlKeycode::J
-        | SdlKeycode::K
-        | SdlKeycode::L
-        | SdlKeycode::M
-        | SdlKeycode::N
-        | SdlKeycode::O
-        | SdlKeycode::P
-        | SdlKeycode::Q
-        | SdlKeycode::R
-        | SdlKeycode::S
-        | SdlKeycode::T
-        | SdlKeycode::U
-        | SdlKeycode::V
-        | SdlKeycode::W
-        | SdlKeycode::X
-        | SdlKeycode::Y
-        | SdlKeycode::Z => BevyKey::Character(keycode.name().into()),
+/// Set on SDL keycodes that don't produce a character.
+const SDLK_SCANCODE_MASK: i32 = 1 << 30;
 
-        // Number keys
-        SdlKeycode::NUM_0
-        | SdlKeycode::NUM_1
-        | SdlKeycode::NUM_2
-        | SdlKeycode::NUM_3
-        | SdlKeycode::NUM_4
-        | SdlKeycode::NUM_5
-        | SdlKeycode::NUM_6
-        | SdlKeycode::NUM_7
-        | SdlKeycode::NUM_8
-        | SdlKeycode::NUM_9 => BevyKey::Character(keycode.name().into()),
+/// Converts an SDL keycode to a logical [`Key`](bevy_input::keyboard::Key), keycodes without one
+/// are unidentified.
+pub fn convert_sdl_keycode(keycode: sdl2::keyboard::Keycode) -> bevy_input::keyboard::Key {
+    use bevy_input::keyboard::{Key as BevyKey, NativeKey};
+    use sdl2::keyboard::Keycode as SdlKeycode;
 
+    #[allow(unreachable_patterns)]
+    match keycode {
         // Function keys
         SdlKeycode::F1 => BevyKey::F1,
         SdlKeycode::F2 => BevyKey::F2,
@@ -366,14 +351,14 @@ pub fn convert_sdl_keycode(keycode: sdl2::keyboard::Keycode) -> Option<bevy_inpu
         // Control keys
         SdlKeycode::BACKSPACE => BevyKey::Backspace,
         SdlKeycode::TAB => BevyKey::Tab,
-        SdlKeycode::RETURN => BevyKey::Enter,
+        SdlKeycode::RETURN | SdlKeycode::RETURN2 => BevyKey::Enter,
         SdlKeycode::ESCAPE => BevyKey::Escape,
         SdlKeycode::SPACE => BevyKey::Space,
         SdlKeycode::DELETE => BevyKey::Delete,
         SdlKeycode::CAPSLOCK => BevyKey::CapsLock,
         SdlKeycode::SCROLLLOCK => BevyKey::ScrollLock,
         SdlKeycode::NUMLOCKCLEAR => BevyKey::NumLock,
-        SdlKeycode::PRINTSCREEN => BevyKey::PrintScreen,
+        SdlKeycode::PRINTSCREEN | SdlKeycode::SYSREQ => BevyKey::PrintScreen,
         SdlKeycode::PAUSE => BevyKey::Pause,
         SdlKeycode::CLEAR => BevyKey::Clear,
         SdlKeycode::SELECT => BevyKey::Select,
@@ -408,56 +393,40 @@ pub fn convert_sdl_keycode(keycode: sdl2::keyboard::Keycode) -> Option<bevy_inpu
         SdlKeycode::AC_REFRESH => BevyKey::BrowserRefresh,
         SdlKeycode::AC_BOOKMARKS => BevyKey::BrowserFavorites,
 
-        // Numpad keys
-        SdlKeycode::KP_0
-        | SdlKeycode::KP_1
-        | SdlKeycode::KP_2
-        | SdlKeycode::KP_3
-        | SdlKeycode::KP_4
-        | SdlKeycode::KP_5
-        | SdlKeycode::KP_6
-        | SdlKeycode::KP_7
-        | SdlKeycode::KP_8
-        | SdlKeycode::KP_9
-        | SdlKeycode::KP_PERIOD
-        | SdlKeycode::KP_DIVIDE
-        | SdlKeycode::KP_MULTIPLY
-        | SdlKeycode::KP_MINUS
-        | SdlKeycode::KP_PLUS
-        | SdlKeycode::KP_EQUALS => BevyKey::Character(keycode.name().into()),
-
-        // Symbol keys
-        SdlKeycode::EXCLAIM
-        | SdlKeycode::QUOTEDBL
-        | SdlKeycode::HASH
-        | SdlKeycode::DOLLAR
-        | SdlKeycode::PERCENT
-        | SdlKeycode::AMPERSAND
-        | SdlKeycode::QUOTE
-        | SdlKeycode::LEFTPAREN
-        | SdlKeycode::RIGHTPAREN
-        | SdlKeycode::ASTERISK
-        | SdlKeycode::PLUS
-        | SdlKeycode::COMMA
-        | SdlKeycode::MINUS
-        | SdlKeycode::PERIOD
-        | SdlKeycode::SLASH
-        | SdlKeycode::COLON
-        | SdlKeycode::SEMICOLON
-        | SdlKeycode::LESS
-        | SdlKeycode::EQUALS
-        | SdlKeycode::GREATER
-        | SdlKeycode::QUESTION
-        | SdlKeycode::AT
-        | SdlKeycode::LEFTBRACKET
-        | SdlKeycode::BACKSLASH
-        | SdlKeycode::RIGHTBRACKET
-        | SdlKeycode::CARET
-        | SdlKeycode::UNDERSCORE
-        | SdlKeycode::BACKQUOTE => BevyKey::Character(keycode.name().into()),
-
-        _ => return None,
-    })
+        // Numpad keys, their keycodes aren't characters
+        SdlKeycode::KP_0 => BevyKey::Character("0".into()),
+        SdlKeycode::KP_1 => BevyKey::Character("1".into()),
+        SdlKeycode::KP_2 => BevyKey::Character("2".into()),
+        SdlKeycode::KP_3 => BevyKey::Character("3".into()),
+        SdlKeycode::KP_4 => BevyKey::Character("4".into()),
+        SdlKeycode::KP_5 => BevyKey::Character("5".into()),
+        SdlKeycode::KP_6 => BevyKey::Character("6".into()),
+        SdlKeycode::KP_7 => BevyKey::Character("7".into()),
+        SdlKeycode::KP_8 => BevyKey::Character("8".into()),
+        SdlKeycode::KP_9 => BevyKey::Character("9".into()),
+        SdlKeycode::KP_PERIOD => BevyKey::Character(".".into()),
+        SdlKeycode::KP_DIVIDE => BevyKey::Character("/".into()),
+        SdlKeycode::KP_MULTIPLY => BevyKey::Character("*".into()),
+        SdlKeycode::KP_MINUS => BevyKey::Character("-".into()),
+        SdlKeycode::KP_PLUS => BevyKey::Character("+".into()),
+        SdlKeycode::KP_EQUALS => BevyKey::Character("=".into()),
+        SdlKeycode::KP_ENTER => BevyKey::Enter,
+        SdlKeycode::KP_TAB => BevyKey::Tab,
+        SdlKeycode::KP_SPACE => BevyKey::Space,
+        SdlKeycode::KP_CLEAR => BevyKey::Clear,
+
+        // Keys that produce a character use it as their keycode, following the keyboard layout
+        _ => {
+            let code = keycode.into_i32();
+            match char::from_u32(code as u32) {
+                Some(character) if code & SDLK_SCANCODE_MASK == 0 && !character.is_control() => {
+                    BevyKey::Character(character.to_string().into())
+                }
+                // Bevy has no native key for SDL keycodes
+                _ => BevyKey::Unidentified(NativeKey::Unidentified),
+            }
+        }
+    }
 }
 
 pub fn convert_sdl_mouse_btn(
@@ -478,8 +447,7 @@ pub fn convert_sdl_mouse_btn(
 pub fn convert_sdl_touch_event(
     phase: bevy_input::touch::TouchPhase,
     finger_id: i64,
-    x: f32,
-    y: f32,
+    position: bevy_math::Vec2,
     pressure: f32,
     window: bevy_ecs::entity::Entity,
 ) -> bevy_input::touch::TouchInput {
@@ -491,8 +459,91 @@ pub fn convert_sdl_touch_event(
     bevy_input::touch::TouchInput {
         phase,
         id: finger_id as u64,
-        position: bevy_math::Vec2::new(x, y),
+        position,
         window,
         force,
     }
 }
+
+pub fn convert_sdl_display_mode(mode: &sdl2::video::DisplayMode) -> bevy_window::VideoMode {
+    bevy_window::VideoMode {
+        physical_size: bevy_math::UVec2::new(mode.w as u32, mode.h as u32),
+        bit_depth: mode
+            .format
+            .into_masks()
+            .map(|masks| masks.bpp as u16)
+            .unwrap_or_default(),
+        refresh_rate_millihertz: mode.refresh_rate as u32 * 1000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_input::keyboard::{Key, KeyCode, NativeKey, NativeKeyCode};
+    use sdl2::keyboard::{Keycode, Scancode};
+
+    #[test]
+    fn scancodes_with_an_equivalent_are_mapped() {
+        assert_eq!(convert_sdl_scancode(Scancode::A), KeyCode::KeyA);
+        assert_eq!(
+            convert_sdl_scancode(Scancode::Computer),
+            KeyCode::LaunchApp1
+        );
+        assert_eq!(
+            convert_sdl_scancode(Scancode::Calculator),
+            KeyCode::LaunchApp2
+        );
+        assert_eq!(convert_sdl_scancode(Scancode::Return), KeyCode::Enter);
+    }
+
+    #[test]
+    fn scancodes_sharing_a_key_code_are_unidentified() {
+        let unidentified = KeyCode::Unidentified(NativeKeyCode::Unidentified);
+        for scancode in [
+            Scancode::App1,
+            Scancode::App2,
+            Scancode::Return2,
+            Scancode::KpTab,
+            Scancode::Mode,
+        ] {
+            assert_eq!(convert_sdl_scancode(scancode), unidentified);
+        }
+    }
+
+    #[test]
+    fn unmapped_keys_are_unidentified() {
+        assert_eq!(
+            convert_sdl_scancode(Scancode::Kp00),
+            KeyCode::Unidentified(NativeKeyCode::Unidentified)
+        );
+        assert_eq!(
+            convert_sdl_keycode(Keycode::EJECT),
+            Key::Unidentified(NativeKey::Unidentified)
+        );
+    }
+
+    #[test]
+    fn character_keycodes_follow_the_layout() {
+        assert_eq!(convert_sdl_keycode(Keycode::A), Key::Character("a".into()));
+        let e_acute = Keycode::from_i32('é' as i32).unwrap();
+        assert_eq!(convert_sdl_keycode(e_acute), Key::Character("é".into()));
+        assert_eq!(
+            convert_sdl_keycode(Keycode::KP_1),
+            Key::Character("1".into())
+        );
+        assert_eq!(convert_sdl_keycode(Keycode::RETURN), Key::Enter);
+        assert_eq!(convert_sdl_keycode(Keycode::KP_ENTER), Key::Enter);
+    }
+
+    #[test]
+    fn unknown_keys_are_unidentified() {
+        assert_eq!(
+            convert_sdl_key(None, None),
+            (
+                KeyCode::Unidentified(NativeKeyCode::Unidentified),
+                Key::Unidentified(NativeKey::Unidentified)
+            )
+        );
+    }
+}
@@ -1,26 +1,39 @@
-use crate::{CachedWindow, SDL_WINDOWS, sdl_windows::SendSyncSdlWindow};
+use crate::cursor::{CachedCursorOptions, apply_cursor_options};
+use crate::hit_test::{WindowHitTest, install_hit_test};
+use crate::input::start_text_input;
+use crate::monitors::{Sdl2Monitor, resolve_window_mode, resolve_window_position};
+use crate::scale_factor::{base_scale_factor, pixel_ratio, set_physical_size};
+use crate::transparency::prepare_window_visual;
+use crate::window_mode::{display_center, select_display, select_display_mode};
+use crate::window_state::Sdl2WindowState;
+use crate::{
+    CachedWindow, SDL_WINDOWS, SdlRequest, SdlRequestSender,
+    sdl_windows::{SdlRawHandles, SendSyncSdlWindow},
+};
 use bevy_ecs::{
     entity::Entity,
     message::MessageWriter,
     query::QueryFilter,
     system::{Commands, Query, SystemParamItem},
 };
+use bevy_math::UVec2;
 use bevy_window::{
-    CursorOptions, RawHandleWrapper, RawHandleWrapperHolder, WindowCreated, WindowWrapper,
+    CursorOptions, Ime, RawHandleWrapper, RawHandleWrapperHolder, WindowCreated, WindowWrapper,
 };
 use crossbeam_channel::Sender;
-use sdl2::VideoSubsystem;
+use sdl2::{VideoSubsystem, sys::SDL_WindowFlags};
+use std::sync::Arc;
 
-pub type WindowReady = (u32, SendSyncSdlWindow);
+pub type WindowReady = (u32, SendSyncSdlWindow, Arc<WindowHitTest>);
 
 pub fn build_sdl_window(
     video_subsystem: &VideoSubsystem,
     window: &bevy_window::Window,
-    cursor_options: &CursorOptions,
     ready_sender: Sender<WindowReady>,
-) {
+) -> (u32, Arc<WindowHitTest>) {
     let mut window_builder =
         video_subsystem.window(&window.title, window.width() as u32, window.height() as u32);
+    window_builder.allow_highdpi();
     if window.resizable {
         window_builder.resizable();
     }
@@ -30,38 +43,137 @@ pub fn build_sdl_window(
     if !window.decorations {
         window_builder.borderless();
     }
-    match window.mode {
-        bevy_window::WindowMode::Windowed => {}
-        bevy_window::WindowMode::BorderlessFullscreen(..) => {
-            window_builder.borderless();
+    match window.window_level {
+        bevy_window::WindowLevel::AlwaysOnBottom => {
+            bevy_log::warn_once!("WindowLevel::AlwaysOnBottom is not supported by SDL2");
+        }
+        bevy_window::WindowLevel::Normal => {}
+        bevy_window::WindowLevel::AlwaysOnTop => {
+            window_builder.always_on_top();
+        }
+    }
+    if window.skip_taskbar {
+        let flags = window_builder.window_flags() | SDL_WindowFlags::SDL_WINDOW_SKIP_TASKBAR as u32;
+        window_builder.set_window_flags(flags);
+    }
+    match window.position {
+        bevy_window::WindowPosition::Automatic => {}
+        bevy_window::WindowPosition::Centered(monitor) => {
+            if let Some((x, y)) =
+                select_display(video_subsystem, None, &monitor).and_then(|display| {
+                    display_center(
+                        video_subsystem,
+                        display,
+                        window.width() as u32,
+                        window.height() as u32,
+                    )
+                })
+            {
+                window_builder.position(x, y);
+            }
+        }
+        bevy_window::WindowPosition::At(position) => {
+            window_builder.position(position.x, position.y);
+        }
+    }
+    // Fullscreen windows are created on the selected display
+    let fullscreen_display = match &window.mode {
+        bevy_window::WindowMode::Windowed => None,
+        bevy_window::WindowMode::BorderlessFullscreen(monitor) => {
             window_builder.fullscreen_desktop();
+            select_display(video_subsystem, None, monitor)
         }
-        bevy_window::WindowMode::Fullscreen(..) => {
+        bevy_window::WindowMode::Fullscreen(monitor, _) => {
             window_builder.fullscreen();
+            select_display(video_subsystem, None, monitor)
         }
+    };
+    if let Some(display) = fullscreen_display
+        && let Some((x, y)) = display_center(
+            video_subsystem,
+            display,
+            window.width() as u32,
+            window.height() as u32,
+        )
+    {
+        window_builder.position(x, y);
     }
 
     #[cfg(target_os = "macos")]
     window_builder.metal_view();
 
+    prepare_window_visual(video_subsystem, window);
+
     let mut sdl_window = window_builder
         .build()
         .map_err(|e| e.to_string())
         .expect("failed to build window");
-    if window.transparent {
-        sdl_window
-            .set_opacity(0.5)
-            .expect("Failed to set window opacity");
-    }
-    match cursor_options.grab_mode {
-        bevy_window::CursorGrabMode::None => sdl_window.set_mouse_grab(false),
-        bevy_window::CursorGrabMode::Confined | bevy_window::CursorGrabMode::Locked => {
-            sdl_window.set_mouse_grab(true);
-        }
+    if let bevy_window::WindowMode::Fullscreen(_, video_mode) = &window.mode
+        && let Some(display_mode) = fullscreen_display
+            .and_then(|display| select_display_mode(video_subsystem, display, video_mode))
+        && let Err(err) = sdl_window.set_display_mode(display_mode)
+    {
+        bevy_log::error!("Failed to set fullscreen display mode: {err}");
     }
 
+    let hit_test = Arc::new(WindowHitTest::default());
+    install_hit_test(&sdl_window, &hit_test);
+
     let sdl_window_id = sdl_window.id();
-    let _ = ready_sender.send((sdl_window_id, SendSyncSdlWindow(sdl_window)));
+    let _ = ready_sender.send((
+        sdl_window_id,
+        SendSyncSdlWindow(sdl_window),
+        hit_test.clone(),
+    ));
+    (sdl_window_id, hit_test)
+}
+
+/// The largest window size given to SDL, in window units.
+///
+/// SDL can't leave the maximum size unbounded, and Windows overflows when it scales sizes close to
+/// `i32::MAX` by the DPI and adds them to the window size.
+const MAX_WINDOW_SIZE: u32 = 16384;
+
+/// Forwards the [`WindowResizeConstraints`](bevy_window::WindowResizeConstraints) of the window to
+/// SDL as its minimum and maximum size.
+///
+/// They are converted with the scale factor of the window, so they must be applied again when it
+/// changes.
+pub(crate) fn apply_resize_constraints(
+    sdl_window: &mut sdl2::video::Window,
+    window: &bevy_window::Window,
+) {
+    let constraints = window.resize_constraints.check_constraints();
+    let scale = window.scale_factor() / pixel_ratio(sdl_window);
+    // Saturating cast, an infinite maximum size is clamped like any other oversized one
+    let to_window_units = |logical: f32| ((logical * scale) as u32).clamp(1, MAX_WINDOW_SIZE);
+    if let Err(err) = sdl_window.set_minimum_size(
+        to_window_units(constraints.min_width),
+        to_window_units(constraints.min_height),
+    ) {
+        bevy_log::error!("Failed to set window minimum size: {err}");
+    }
+    if let Err(err) = sdl_window.set_maximum_size(
+        to_window_units(constraints.max_width),
+        to_window_units(constraints.max_height),
+    ) {
+        bevy_log::error!("Failed to set window maximum size: {err}");
+    }
+}
+
+/// Clamps the logical size of the window to its
+/// [`WindowResizeConstraints`](bevy_window::WindowResizeConstraints).
+pub(crate) fn clamp_to_resize_constraints(window: &mut bevy_window::Window) {
+    let constraints = window.resize_constraints.check_constraints();
+    let width = window
+        .width()
+        .clamp(constraints.min_width, constraints.max_width);
+    let height = window
+        .height()
+        .clamp(constraints.min_height, constraints.max_height);
+    if width != window.width() || height != window.height() {
+        window.resolution.set(width, height);
+    }
 }
 
 pub type CreateWindowParams<'w, 's, F = ()> = (
@@ -78,31 +190,64 @@ pub type CreateWindowParams<'w, 's, F = ()> = (
         F,
     >,
     MessageWriter<'w, WindowCreated>,
+    MessageWriter<'w, Ime>,
+    Query<'w, 's, &'static Sdl2Monitor>,
 );
 
 pub fn create_windows<F: QueryFilter + 'static>(
-    (mut commands, mut created_windows, mut window_created_events): SystemParamItem<
-        CreateWindowParams<F>,
-    >,
-    sender: &Sender<(
-        Entity,
-        bevy_window::Window,
-        CursorOptions,
-        Sender<WindowReady>,
-    )>,
+    (
+        mut commands,
+        mut created_windows,
+        mut window_created_events,
+        mut ime_messages,
+        monitors,
+    ): SystemParamItem<CreateWindowParams<F>>,
+    sender: &SdlRequestSender,
 ) {
-    for (entity, window, cursor_options, maybe_handle_holder) in &mut created_windows {
+    for (entity, mut window, cursor_options, maybe_handle_holder) in &mut created_windows {
         if SDL_WINDOWS.with_borrow(|windows| windows.entity_to_sdl_window.contains_key(&entity)) {
             continue;
         }
 
+        clamp_to_resize_constraints(&mut window);
+
+        // The SDL thread can't read monitor entities
+        let mut sdl_window_settings = window.clone();
+        sdl_window_settings.mode = resolve_window_mode(window.mode, &monitors);
+        sdl_window_settings.position = resolve_window_position(window.position, &monitors);
+
         let (ready_sender, ready_receiver) = crossbeam_channel::bounded(1);
-        let _ = sender.send((entity, window.clone(), cursor_options.clone(), ready_sender));
+        let _ = sender.send(SdlRequest::CreateWindow(
+            entity,
+            Box::new(sdl_window_settings),
+            ready_sender,
+        ));
 
-        let (sdl_window_id, sdl_window) =
+        let (sdl_window_id, mut sdl_window, hit_test) =
             ready_receiver.recv().expect("Failed to create SDL window");
 
-        let raw_handle_wrapper = RawHandleWrapper::new(&WindowWrapper::new(sdl_window.clone()))
+        // The window was created with its logical size in SDL window units, this only matches its
+        // physical size when the OS scales windows
+        window
+            .resolution
+            .set_scale_factor_and_apply_to_physical_size(base_scale_factor(&sdl_window));
+        set_physical_size(
+            &mut sdl_window,
+            UVec2::new(window.physical_width(), window.physical_height()),
+        );
+        apply_resize_constraints(&mut sdl_window, &window);
+        apply_cursor_options(&mut sdl_window, cursor_options, &CursorOptions::default());
+        // Later changes are applied by comparing with the cached window, which starts out equal
+        if window.ime_enabled {
+            ime_messages.write(Ime::Enabled { window: entity });
+        }
+        if sdl_window.window_flags() & SDL_WindowFlags::SDL_WINDOW_INPUT_FOCUS as u32 != 0 {
+            start_text_input(&sdl_window, &window);
+        }
+
+        // The render world only gets the raw handles, the SDL window stays on our threads
+        let raw_handle_wrapper = SdlRawHandles::new(&sdl_window)
+            .and_then(|handles| RawHandleWrapper::new(&WindowWrapper::new(handles)))
             .expect("Failed to create raw handle wrapper");
 
         if let Some(handle_holder) = maybe_handle_holder {
@@ -110,7 +255,11 @@ pub fn create_windows<F: QueryFilter + 'static>(
             *handle_holder.0.lock().unwrap() = Some(raw_handle_wrapper);
         }
 
-        commands.entity(entity).insert(CachedWindow(window.clone()));
+        commands.entity(entity).insert((
+            CachedWindow(window.clone()),
+            CachedCursorOptions(cursor_options.clone()),
+            Sdl2WindowState::from_sdl_window(&sdl_window),
+        ));
 
         window_created_events.write(WindowCreated { window: entity });
         bevy_log::info!("window created {entity}");
@@ -119,6 +268,7 @@ pub fn create_windows<F: QueryFilter + 'static>(
             windows.windows.insert(sdl_window_id, sdl_window);
             windows.entity_to_sdl_window.insert(entity, sdl_window_id);
             windows.sdl_window_to_entity.insert(sdl_window_id, entity);
+            windows.hit_tests.insert(sdl_window_id, hit_test);
         });
     }
 }
@@ -1,71 +1,478 @@
 use core::cell::RefCell;
 
+use bevy_asset::{AssetEvent, AssetId, Assets};
 use bevy_ecs::{
+    component::Component,
     entity::Entity,
+    message::MessageReader,
     query::Changed,
-    system::{NonSendMarker, Query},
+    system::{NonSendMarker, Query, Res},
 };
-use bevy_window::{CursorIcon, Window};
+use bevy_image::{Image, TextureAtlasLayout};
+use bevy_math::URect;
+use bevy_platform::collections::{HashMap, hash_map::Entry};
+use bevy_window::{
+    CursorGrabMode, CursorIcon, CursorOptions, CustomCursor, CustomCursorImage, SystemCursorIcon,
+    Window,
+};
+use sdl2::{
+    pixels::PixelFormatEnum,
+    surface::Surface,
+    sys::{SDL_WindowFlags, SDL_bool},
+};
+
+use crate::SDL_WINDOWS;
 
 thread_local! {
-    static ACTIVE_CURSOR: RefCell<Option<sdl2::mouse::Cursor>> = RefCell::new(None);
+    static CURSORS: RefCell<CursorCache> = RefCell::new(CursorCache::default());
 }
 
-pub(crate) fn set_cursor(
-    q: Query<(Entity, &Window, &CursorIcon)>,
-    has_changed: Query<(), Changed<CursorIcon>>,
-    _marker: NonSendMarker,
-) {
-    for (entity, window, cursor_icon) in &q {
-        // We only want to update the cursor on the window that has a cursor
-        if window.physical_cursor_position().is_none() {
-            continue;
+/// SDL cursors created so far, they are kept alive so switching between them is cheap.
+#[derive(Default)]
+struct CursorCache {
+    system: HashMap<sdl2::mouse::SystemCursor, sdl2::mouse::Cursor>,
+    custom: HashMap<CustomCursorKey, sdl2::mouse::Cursor>,
+    /// The icon of the cursor currently set in SDL.
+    active: Option<CursorIcon>,
+}
+
+/// A [`CustomCursorImage`] without its handles, so cached cursors don't keep their image alive and
+/// can be dropped once it's unused.
+#[derive(PartialEq, Eq, Hash)]
+struct CustomCursorKey {
+    image: AssetId<Image>,
+    texture_atlas: Option<(AssetId<TextureAtlasLayout>, usize)>,
+    flip_x: bool,
+    flip_y: bool,
+    rect: Option<URect>,
+    hotspot: (u16, u16),
+}
+
+impl From<&CustomCursorImage> for CustomCursorKey {
+    fn from(custom_cursor: &CustomCursorImage) -> Self {
+        Self {
+            image: custom_cursor.handle.id(),
+            texture_atlas: custom_cursor
+                .texture_atlas
+                .as_ref()
+                .map(|atlas| (atlas.layout.id(), atlas.index)),
+            flip_x: custom_cursor.flip_x,
+            flip_y: custom_cursor.flip_y,
+            rect: custom_cursor.rect,
+            hotspot: custom_cursor.hotspot,
         }
-        ACTIVE_CURSOR.with_borrow_mut(|active_cursor| {
-            let set_cursor = |active_cursor: &mut Option<sdl2::mouse::Cursor>,
-                              cursor_icon: &CursorIcon| {
-                let sdl_cursor = match cursor_icon {
-                    CursorIcon::System(system_cursor_icon) => {
-                        if let Some(sys_cursor) = map_bevy_system_cursor_to_sdl(system_cursor_icon)
-                        {
-                            sdl2::mouse::Cursor::from_system(sys_cursor)
-                        } else {
-                            return;
-                        }
-                    }
-                    #[allow(unreachable_patterns)]
-                    _ => {
-                        bevy_log::warn_once!("Custom cursor icon are not supported");
-                        return;
+    }
+}
+
+impl CursorCache {
+    fn set(
+        &mut self,
+        cursor_icon: &CursorIcon,
+        images: Option<&Assets<Image>>,
+        layouts: Option<&Assets<TextureAtlasLayout>>,
+    ) {
+        if self.active.as_ref() == Some(cursor_icon) {
+            return;
+        }
+        let cursor = match cursor_icon {
+            CursorIcon::System(system_cursor_icon) => {
+                self.system_cursor(map_bevy_system_cursor_to_sdl(system_cursor_icon))
+            }
+            CursorIcon::Custom(CustomCursor::Image(custom_cursor)) => {
+                match self.custom_cursor(custom_cursor, images, layouts) {
+                    Ok(Some(cursor)) => Some(cursor),
+                    // Try again next frame
+                    Ok(None) => return,
+                    Err(err) => {
+                        bevy_log::error!("Failed to create custom cursor: {err}");
+                        None
                     }
                 }
-                .expect("Failed to create cursor");
-                sdl_cursor.set();
-                *active_cursor = Some(sdl_cursor);
-            };
-            if active_cursor.is_some() {
-                if has_changed.get(entity).is_ok() {
-                    set_cursor(active_cursor, cursor_icon)
+            }
+            CursorIcon::Custom(CustomCursor::Url(_)) => {
+                bevy_log::warn_once!("Custom cursor URLs are only supported on the web");
+                None
+            }
+        };
+        if let Some(cursor) = cursor {
+            cursor.set();
+        }
+        // Don't try again every frame if the cursor couldn't be created
+        self.active = Some(cursor_icon.clone());
+    }
+
+    fn system_cursor(
+        &mut self,
+        system_cursor: sdl2::mouse::SystemCursor,
+    ) -> Option<&sdl2::mouse::Cursor> {
+        match self.system.entry(system_cursor) {
+            Entry::Occupied(entry) => Some(entry.into_mut()),
+            Entry::Vacant(entry) => match sdl2::mouse::Cursor::from_system(system_cursor) {
+                Ok(cursor) => Some(entry.insert(cursor)),
+                Err(err) => {
+                    bevy_log::error!("Failed to create {system_cursor:?} cursor: {err}");
+                    None
                 }
-            } else {
-                // This should only happen once on startup
-                set_cursor(active_cursor, cursor_icon)
+            },
+        }
+    }
+
+    /// Returns the cursor for a custom image, or `None` if the image isn't loaded yet.
+    fn custom_cursor(
+        &mut self,
+        custom_cursor: &CustomCursorImage,
+        images: Option<&Assets<Image>>,
+        layouts: Option<&Assets<TextureAtlasLayout>>,
+    ) -> Result<Option<&sdl2::mouse::Cursor>, String> {
+        let entry = match self.custom.entry(CustomCursorKey::from(custom_cursor)) {
+            Entry::Occupied(entry) => return Ok(Some(entry.into_mut())),
+            Entry::Vacant(entry) => entry,
+        };
+        let Some(mut pixels) = CursorPixels::extract(custom_cursor, images, layouts)? else {
+            return Ok(None);
+        };
+        let surface = Surface::from_data(
+            &mut pixels.pixels,
+            pixels.width,
+            pixels.height,
+            pixels.width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        // SDL copies the pixels so the surface can be dropped right away
+        let cursor =
+            sdl2::mouse::Cursor::from_surface(surface, pixels.hotspot.0, pixels.hotspot.1)?;
+        Ok(Some(entry.insert(cursor)))
+    }
+
+    /// Drops the cursors created from an image, they are created again from its new pixels if it's
+    /// still used.
+    fn invalidate_image(&mut self, image: AssetId<Image>) {
+        self.custom.retain(|key, _| key.image != image);
+        if let Some(CursorIcon::Custom(CustomCursor::Image(custom_cursor))) = &self.active
+            && custom_cursor.handle.id() == image
+        {
+            self.active = None;
+        }
+    }
+}
+
+/// RGBA pixels of a custom cursor, ready to be handed to SDL.
+struct CursorPixels {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    hotspot: (i32, i32),
+}
+
+impl CursorPixels {
+    /// Crops and flips the image of a custom cursor, or returns `None` if the image or its texture
+    /// atlas layout isn't loaded yet.
+    fn extract(
+        custom_cursor: &CustomCursorImage,
+        images: Option<&Assets<Image>>,
+        layouts: Option<&Assets<TextureAtlasLayout>>,
+    ) -> Result<Option<Self>, String> {
+        let Some(image) = images.and_then(|images| images.get(&custom_cursor.handle)) else {
+            return Ok(None);
+        };
+        let atlas_rect = match &custom_cursor.texture_atlas {
+            Some(atlas) => {
+                let Some(layout) = layouts.and_then(|layouts| layouts.get(&atlas.layout)) else {
+                    return Ok(None);
+                };
+                let rect = layout.textures.get(atlas.index).ok_or_else(|| {
+                    format!("texture atlas index {} is out of bounds", atlas.index)
+                })?;
+                Some(*rect)
+            }
+            None => None,
+        };
+        // The rect is relative to the texture atlas section
+        let rect = match (atlas_rect, custom_cursor.rect) {
+            (Some(atlas_rect), Some(rect)) => Some(URect::from_corners(
+                atlas_rect.min + rect.min,
+                (atlas_rect.min + rect.max).min(atlas_rect.max),
+            )),
+            (atlas_rect, rect) => atlas_rect.or(rect),
+        };
+
+        let mut dynamic = image
+            .clone()
+            .try_into_dynamic()
+            .map_err(|err| err.to_string())?;
+        if let Some(rect) = rect {
+            let rect = rect.intersect(URect::new(0, 0, dynamic.width(), dynamic.height()));
+            if rect.is_empty() {
+                return Err("the cursor rect is outside of the image".to_string());
             }
-        });
+            dynamic = dynamic.crop_imm(rect.min.x, rect.min.y, rect.width(), rect.height());
+        }
+        if custom_cursor.flip_x {
+            dynamic = dynamic.fliph();
+        }
+        if custom_cursor.flip_y {
+            dynamic = dynamic.flipv();
+        }
+        let rgba = dynamic.into_rgba8();
+        let (width, height) = (rgba.width(), rgba.height());
+
+        // The hotspot is given in the unflipped image
+        let (mut hot_x, mut hot_y) = (
+            (custom_cursor.hotspot.0 as u32).min(width - 1),
+            (custom_cursor.hotspot.1 as u32).min(height - 1),
+        );
+        if custom_cursor.flip_x {
+            hot_x = width - 1 - hot_x;
+        }
+        if custom_cursor.flip_y {
+            hot_y = height - 1 - hot_y;
+        }
+        Ok(Some(CursorPixels {
+            width,
+            height,
+            pixels: rgba.into_raw(),
+            hotspot: (hot_x as i32, hot_y as i32),
+        }))
+    }
+}
+
+/// Sets the SDL cursor to the [`CursorIcon`] of the window under the pointer.
+///
+/// SDL has a single cursor for all windows, so it's switched when the pointer moves to a window
+/// with a different icon. Custom cursors are created once their image is loaded.
+pub(crate) fn set_cursor(
+    windows: Query<(&Window, Option<&CursorIcon>)>,
+    images: Option<Res<Assets<Image>>>,
+    layouts: Option<Res<Assets<TextureAtlasLayout>>>,
+    image_events: Option<MessageReader<AssetEvent<Image>>>,
+    _marker: NonSendMarker,
+) {
+    // Cursors of hot reloaded images are outdated, and those of unused images are never set again
+    if let Some(mut image_events) = image_events {
+        for event in image_events.read() {
+            if let AssetEvent::Modified { id }
+            | AssetEvent::Removed { id }
+            | AssetEvent::Unused { id } = event
+            {
+                CURSORS.with_borrow_mut(|cursors| cursors.invalidate_image(*id));
+            }
+        }
+    }
+    let Some((_, cursor_icon)) = windows
+        .iter()
+        .find(|(window, _)| window.physical_cursor_position().is_some())
+    else {
         return;
+    };
+    // Windows without a cursor icon use the default cursor
+    let cursor_icon = cursor_icon.cloned().unwrap_or_default();
+    CURSORS.with_borrow_mut(|cursors| {
+        cursors.set(&cursor_icon, images.as_deref(), layouts.as_deref());
+    });
+}
+
+/// The cursor options last applied to SDL so we can check which ones were changed from within the
+/// app.
+#[derive(Debug, Clone, Component)]
+pub(crate) struct CachedCursorOptions(pub(crate) CursorOptions);
+
+/// Applies the [`CursorOptions`] that differ from `previous` to an SDL window.
+///
+/// Cursor visibility and relative mouse mode are global in SDL, so they are only applied while
+/// the window has focus, see [`apply_focused_cursor_options`].
+pub(crate) fn apply_cursor_options(
+    sdl_window: &mut sdl2::video::Window,
+    cursor_options: &CursorOptions,
+    previous: &CursorOptions,
+) {
+    if cursor_options.grab_mode != previous.grab_mode {
+        sdl_window.set_mouse_grab(cursor_options.grab_mode == CursorGrabMode::Confined);
+    }
+    let has_focus = sdl_window.window_flags() & SDL_WindowFlags::SDL_WINDOW_INPUT_FOCUS as u32 != 0;
+    if has_focus
+        && (cursor_options.visible != previous.visible
+            || cursor_options.grab_mode != previous.grab_mode)
+    {
+        apply_focused_cursor_options(cursor_options);
+    }
+}
+
+/// Applies the global cursor state of the focused window, when it gains focus or its
+/// [`CursorOptions`] change.
+///
+/// This calls SDL directly, the safe mouse API clones the SDL context owned by the SDL thread.
+pub(crate) fn apply_focused_cursor_options(cursor_options: &CursorOptions) {
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    unsafe { sdl2::sys::SDL_ShowCursor(cursor_options.visible as i32) };
+    // Relative mode keeps sending mouse motion when the cursor reaches the edge of the screen
+    let relative = if cursor_options.grab_mode == CursorGrabMode::Locked {
+        SDL_bool::SDL_TRUE
+    } else {
+        SDL_bool::SDL_FALSE
+    };
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    if unsafe { sdl2::sys::SDL_SetRelativeMouseMode(relative) } != 0 {
+        bevy_log::error!("Failed to set relative mouse mode: {}", sdl2::get_error());
     }
 }
 
-fn map_bevy_system_cursor_to_sdl(
-    system_cursor: &bevy_window::SystemCursorIcon,
-) -> Option<sdl2::mouse::SystemCursor> {
-    Some(match system_cursor {
-        bevy_window::SystemCursorIcon::Default => sdl2::mouse::SystemCursor::Arrow,
-        bevy_window::SystemCursorIcon::Crosshair => sdl2::mouse::SystemCursor::Crosshair,
-        bevy_window::SystemCursorIcon::Text => sdl2::mouse::SystemCursor::IBeam,
-        cursor_icon => {
-            bevy_log::warn_once!("{cursor_icon:?} is not supported");
-            return None;
+/// Applies [`CursorOptions`] changes made from within the app.
+pub(crate) fn changed_cursor_options(
+    mut changed_cursor_options: Query<
+        (Entity, &CursorOptions, &mut CachedCursorOptions),
+        Changed<CursorOptions>,
+    >,
+    _non_send_marker: NonSendMarker,
+) {
+    SDL_WINDOWS.with_borrow_mut(|windows| {
+        for (entity, cursor_options, mut cache) in &mut changed_cursor_options {
+            let Some(sdl_window) = windows.get_window_mut(entity) else {
+                continue;
+            };
+            apply_cursor_options(sdl_window, cursor_options, &cache.0);
+            cache.0 = cursor_options.clone();
+        }
+    });
+}
+
+/// Returns the SDL cursor closest to a [`SystemCursorIcon`].
+///
+/// SDL2 only has 12 system cursors:
+/// - single direction resize cursors use the matching two directions cursor
+/// - `Grab` and `Grabbing` use the hand, `Move` and `AllScroll` the four directions cursor
+/// - `Progress` uses the arrow with a busy indicator, `NoDrop` the "not allowed" cursor
+/// - `Cell` uses the crosshair and `VerticalText` the text cursor
+/// - `ContextMenu`, `Help`, `Alias`, `Copy`, `ZoomIn` and `ZoomOut` use the default arrow
+fn map_bevy_system_cursor_to_sdl(system_cursor: &SystemCursorIcon) -> sdl2::mouse::SystemCursor {
+    use sdl2::mouse::SystemCursor;
+    match system_cursor {
+        SystemCursorIcon::Default
+        | SystemCursorIcon::ContextMenu
+        | SystemCursorIcon::Help
+        | SystemCursorIcon::Alias
+        | SystemCursorIcon::Copy
+        | SystemCursorIcon::ZoomIn
+        | SystemCursorIcon::ZoomOut => SystemCursor::Arrow,
+        SystemCursorIcon::Pointer | SystemCursorIcon::Grab | SystemCursorIcon::Grabbing => {
+            SystemCursor::Hand
         }
-    })
+        SystemCursorIcon::Progress => SystemCursor::WaitArrow,
+        SystemCursorIcon::Wait => SystemCursor::Wait,
+        SystemCursorIcon::Cell | SystemCursorIcon::Crosshair => SystemCursor::Crosshair,
+        SystemCursorIcon::Text | SystemCursorIcon::VerticalText => SystemCursor::IBeam,
+        SystemCursorIcon::Move | SystemCursorIcon::AllScroll => SystemCursor::SizeAll,
+        SystemCursorIcon::NoDrop | SystemCursorIcon::NotAllowed => SystemCursor::No,
+        SystemCursorIcon::EResize
+        | SystemCursorIcon::WResize
+        | SystemCursorIcon::EwResize
+        | SystemCursorIcon::ColResize => SystemCursor::SizeWE,
+        SystemCursorIcon::NResize
+        | SystemCursorIcon::SResize
+        | SystemCursorIcon::NsResize
+        | SystemCursorIcon::RowResize => SystemCursor::SizeNS,
+        SystemCursorIcon::NeResize | SystemCursorIcon::SwResize | SystemCursorIcon::NeswResize => {
+            SystemCursor::SizeNESW
+        }
+        SystemCursorIcon::NwResize | SystemCursorIcon::SeResize | SystemCursorIcon::NwseResize => {
+            SystemCursor::SizeNWSE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_asset::RenderAssetUsages;
+    use sdl2::mouse::SystemCursor;
+    use wgpu::{Extent3d, TextureDimension, TextureFormat};
+
+    #[test]
+    fn system_cursors_map_to_the_closest_sdl_cursor() {
+        let map = |icon| map_bevy_system_cursor_to_sdl(&icon);
+        assert_eq!(map(SystemCursorIcon::Default), SystemCursor::Arrow);
+        assert_eq!(map(SystemCursorIcon::Help), SystemCursor::Arrow);
+        assert_eq!(map(SystemCursorIcon::Pointer), SystemCursor::Hand);
+        assert_eq!(map(SystemCursorIcon::Grabbing), SystemCursor::Hand);
+        assert_eq!(map(SystemCursorIcon::Progress), SystemCursor::WaitArrow);
+        assert_eq!(map(SystemCursorIcon::VerticalText), SystemCursor::IBeam);
+        assert_eq!(map(SystemCursorIcon::NotAllowed), SystemCursor::No);
+        assert_eq!(map(SystemCursorIcon::ColResize), SystemCursor::SizeWE);
+        assert_eq!(map(SystemCursorIcon::RowResize), SystemCursor::SizeNS);
+        assert_eq!(map(SystemCursorIcon::SwResize), SystemCursor::SizeNESW);
+        assert_eq!(map(SystemCursorIcon::SeResize), SystemCursor::SizeNWSE);
+    }
+
+    /// A 4x2 image whose pixels have their index as red channel.
+    fn cursor_image() -> (Assets<Image>, CustomCursorImage) {
+        let pixels = (0..8).flat_map(|index| [index, 0, 0, 255]).collect();
+        let image = Image::new(
+            Extent3d {
+                width: 4,
+                height: 2,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            pixels,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        );
+        let mut images = Assets::default();
+        let custom_cursor = CustomCursorImage {
+            handle: images.add(image),
+            ..Default::default()
+        };
+        (images, custom_cursor)
+    }
+
+    fn extract(images: &Assets<Image>, custom_cursor: &CustomCursorImage) -> CursorPixels {
+        CursorPixels::extract(custom_cursor, Some(images), None)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn hotspot_flips_with_the_image() {
+        let (images, mut custom_cursor) = cursor_image();
+        custom_cursor.hotspot = (1, 0);
+        custom_cursor.flip_x = true;
+        let cursor = extract(&images, &custom_cursor);
+        assert_eq!(cursor.hotspot, (2, 0));
+        // The first pixel was the last one of the first row
+        assert_eq!(cursor.pixels[0], 3);
+
+        custom_cursor.flip_x = false;
+        custom_cursor.flip_y = true;
+        let cursor = extract(&images, &custom_cursor);
+        assert_eq!(cursor.hotspot, (1, 1));
+        assert_eq!(cursor.pixels[0], 4);
+    }
+
+    #[test]
+    fn hotspot_flips_within_the_cropped_rect() {
+        let (images, mut custom_cursor) = cursor_image();
+        custom_cursor.rect = Some(URect::new(2, 0, 4, 2));
+        custom_cursor.hotspot = (0, 1);
+        custom_cursor.flip_x = true;
+        let cursor = extract(&images, &custom_cursor);
+        assert_eq!((cursor.width, cursor.height), (2, 2));
+        assert_eq!(cursor.hotspot, (1, 1));
+        assert_eq!(cursor.pixels[0], 3);
+    }
+
+    #[test]
+    fn hotspot_is_clamped_to_the_image() {
+        let (images, mut custom_cursor) = cursor_image();
+        custom_cursor.hotspot = (10, 10);
+        assert_eq!(extract(&images, &custom_cursor).hotspot, (3, 1));
+    }
+
+    #[test]
+    fn cursor_waits_for_its_image() {
+        let (_, custom_cursor) = cursor_image();
+        let images = Assets::default();
+        assert!(matches!(
+            CursorPixels::extract(&custom_cursor, Some(&images), None),
+            Ok(None)
+        ));
+    }
 }
@@ -0,0 +1,74 @@
+use bevy_app::AppExit;
+use bevy_ecs::{
+    entity::Entity,
+    lifecycle::RemovedComponents,
+    message::{MessageReader, MessageWriter},
+    query::With,
+    system::{Local, NonSendMarker, Query, Res},
+};
+use bevy_window::{ClosingWindow, Window, WindowClosed, WindowClosing, WindowDestroyed};
+
+use crate::{SDL_WINDOWS, SdlRequest, SdlRequestSender, sdl_windows::SendSyncSdlWindow};
+
+/// Tears down the SDL windows of despawned [`Window`] entities.
+///
+/// Closing a window is spread over multiple frames:
+/// - [`WindowClosing`] is written while the entity is marked with [`ClosingWindow`]
+/// - [`WindowClosed`] is written once the [`Window`] component is gone and the SDL window has
+///   been removed from [`SDL_WINDOWS`]
+/// - [`WindowDestroyed`] is written on the next frame, when the SDL window is sent back to the SDL
+///   thread to be dropped
+#[expect(clippy::too_many_arguments, reason = "bevy system")]
+pub(crate) fn despawn_windows(
+    closing: Query<Entity, With<ClosingWindow>>,
+    mut closed: RemovedComponents<Window>,
+    window_entities: Query<Entity, With<Window>>,
+    mut closing_messages: MessageWriter<WindowClosing>,
+    mut closed_messages: MessageWriter<WindowClosed>,
+    mut destroyed_messages: MessageWriter<WindowDestroyed>,
+    mut windows_to_drop: Local<Vec<(Entity, SendSyncSdlWindow)>>,
+    mut exit_messages: MessageReader<AppExit>,
+    sdl_requests: Res<SdlRequestSender>,
+    _non_send_marker: NonSendMarker,
+) {
+    // The render world dropped the surfaces of the windows removed last frame, so they can be
+    // destroyed. SDL windows must be destroyed on the thread that created them.
+    for (entity, sdl_window) in windows_to_drop.drain(..) {
+        if sdl_requests
+            .send(SdlRequest::DestroyWindow(sdl_window))
+            .is_err()
+        {
+            bevy_log::error!("Failed to send window {entity} to the SDL thread");
+            continue;
+        }
+        destroyed_messages.write(WindowDestroyed { window: entity });
+    }
+
+    for window in closing.iter() {
+        closing_messages.write(WindowClosing { window });
+    }
+
+    for window in closed.read() {
+        // The component may have been removed and added again in the same frame
+        if window_entities.contains(window) {
+            continue;
+        }
+        bevy_log::info!("Closing window {window}");
+        if let Some(sdl_window) =
+            SDL_WINDOWS.with_borrow_mut(|windows| windows.remove_window(window))
+        {
+            // Keep the window alive for one more frame, the render world may still have a surface
+            // created from its raw handles
+            windows_to_drop.push((window, sdl_window));
+        }
+        closed_messages.write(WindowClosed { window });
+    }
+
+    // Let the render world know that every window is about to close
+    if !exit_messages.is_empty() {
+        exit_messages.clear();
+        for window in window_entities.iter() {
+            closing_messages.write(WindowClosing { window });
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use core::ffi::c_void;
+use std::sync::{Arc, Mutex};
+
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    lifecycle::RemovedComponents,
+    query::{Added, Changed, Or},
+    system::{NonSendMarker, Query},
+};
+use bevy_math::{CompassOctant, Rect, Vec2};
+use bevy_window::Window;
+use sdl2::sys::{SDL_HitTestResult, SDL_Point, SDL_Window};
+
+use crate::{CachedWindow, SDL_WINDOWS, scale_factor::pixel_ratio};
+
+/// Declares regions of a window that move or resize it when clicked, like a custom title bar or
+/// resize borders on a window without decorations.
+///
+/// Add this component to a [`Window`] entity. The regions are evaluated by SDL when the window is
+/// clicked so they work without any per frame mouse handling. Clicks in these regions are handled
+/// by the OS and are not sent to the app.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct Sdl2HitTestRegions {
+    /// Thickness of the resize borders along the edges of the window, in logical pixels.
+    ///
+    /// Resize borders take priority over `regions`.
+    pub resize_border: Option<f32>,
+    /// Rectangles in logical pixels relative to the top left corner of the window.
+    ///
+    /// The first region containing the clicked point is used. Use [`f32::INFINITY`] to extend a
+    /// region to the right or bottom edge of the window.
+    pub regions: Vec<HitTestRegion>,
+}
+
+/// A rectangle of a window and how it behaves when clicked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitTestRegion {
+    /// The rectangle in logical pixels relative to the top left corner of the window.
+    pub rect: Rect,
+    /// How the rectangle behaves when clicked.
+    pub area: HitTestArea,
+}
+
+/// How a point of a window behaves when it's clicked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitTestArea {
+    /// Clicking drags the window.
+    Draggable,
+    /// Clicking resizes the window in the given direction.
+    Resize(CompassOctant),
+}
+
+impl HitTestArea {
+    fn to_sdl(self) -> SDL_HitTestResult {
+        match self {
+            HitTestArea::Draggable => SDL_HitTestResult::SDL_HITTEST_DRAGGABLE,
+            HitTestArea::Resize(direction) => match direction {
+                CompassOctant::North => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOP,
+                CompassOctant::NorthEast => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPRIGHT,
+                CompassOctant::East => SDL_HitTestResult::SDL_HITTEST_RESIZE_RIGHT,
+                CompassOctant::SouthEast => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMRIGHT,
+                CompassOctant::South => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOM,
+                CompassOctant::SouthWest => SDL_HitTestResult::SDL_HITTEST_RESIZE_BOTTOMLEFT,
+                CompassOctant::West => SDL_HitTestResult::SDL_HITTEST_RESIZE_LEFT,
+                CompassOctant::NorthWest => SDL_HitTestResult::SDL_HITTEST_RESIZE_TOPLEFT,
+            },
+        }
+    }
+}
+
+/// Hit test state of a window, shared between the bevy thread and the SDL hit test callback.
+///
+/// SDL asks the OS to move or resize a window from the hit test callback, when the window is
+/// clicked. The click that made the app call [`Window::start_drag_move`] or
+/// [`Window::start_drag_resize`] has already been handled by then, so the request is armed and the
+/// drag starts on the next left click in the window. This means a drag takes two clicks: releasing
+/// the button and clicking again in place starts it. The request is dropped as soon as the mouse
+/// moves without a button held, so it doesn't linger and show resize cursors while hovering.
+///
+/// The window can also have persistent regions declared with [`Sdl2HitTestRegions`].
+///
+/// [`Window::start_drag_move`]: bevy_window::Window::start_drag_move
+/// [`Window::start_drag_resize`]: bevy_window::Window::start_drag_resize
+#[derive(Default)]
+pub(crate) struct WindowHitTest {
+    state: Mutex<HitTestState>,
+}
+
+/// Hit test state in SDL window units.
+#[derive(Default)]
+struct HitTestState {
+    armed: Option<HitTestArea>,
+    resize_border: Option<f32>,
+    regions: Vec<HitTestRegion>,
+    /// The logical regions and scale the state was last computed from.
+    applied: Option<(Sdl2HitTestRegions, f32)>,
+}
+
+impl WindowHitTest {
+    /// Makes the whole window behave like `area` until the next left click.
+    pub(crate) fn arm(&self, area: HitTestArea) {
+        self.state.lock().unwrap().armed = Some(area);
+    }
+
+    /// Drops the request of [`WindowHitTest::arm`] if it wasn't used yet.
+    pub(crate) fn disarm(&self) {
+        self.state.lock().unwrap().armed = None;
+    }
+
+    /// Replaces the persistent regions of the window, `scale` converts logical pixels to SDL
+    /// window units.
+    fn set_regions(&self, regions: &Sdl2HitTestRegions, scale: f32) {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .applied
+            .as_ref()
+            .is_some_and(|(applied, applied_scale)| applied == regions && *applied_scale == scale)
+        {
+            return;
+        }
+        state.applied = Some((regions.clone(), scale));
+        state.resize_border = regions.resize_border.map(|thickness| thickness * scale);
+        state.regions = regions
+            .regions
+            .iter()
+            .map(|region| HitTestRegion {
+                rect: Rect::from_corners(region.rect.min * scale, region.rect.max * scale),
+                area: region.area,
+            })
+            .collect();
+    }
+
+    fn hit_test(&self, point: Vec2, window_size: Vec2) -> Option<HitTestArea> {
+        let mut state = self.state.lock().unwrap();
+        // The callback also runs when the mouse hovers the window on some platforms, only consume
+        // the request once it's used for a click
+        if state.armed.is_some() {
+            return if is_left_button_pressed() {
+                state.armed.take()
+            } else {
+                state.armed
+            };
+        }
+        if let Some(thickness) = state.resize_border
+            && let Some(direction) = border_direction(point, window_size, thickness)
+        {
+            return Some(HitTestArea::Resize(direction));
+        }
+        state
+            .regions
+            .iter()
+            .find(|region| region.rect.contains(point))
+            .map(|region| region.area)
+    }
+}
+
+/// Returns the direction of the resize border under `point`, if any.
+fn border_direction(point: Vec2, window_size: Vec2, thickness: f32) -> Option<CompassOctant> {
+    let left = point.x < thickness;
+    let right = point.x >= window_size.x - thickness;
+    let top = point.y < thickness;
+    let bottom = point.y >= window_size.y - thickness;
+    Some(match (top, bottom, left, right) {
+        (true, _, true, _) => CompassOctant::NorthWest,
+        (true, _, _, true) => CompassOctant::NorthEast,
+        (_, true, true, _) => CompassOctant::SouthWest,
+        (_, true, _, true) => CompassOctant::SouthEast,
+        (true, _, _, _) => CompassOctant::North,
+        (_, true, _, _) => CompassOctant::South,
+        (_, _, true, _) => CompassOctant::West,
+        (_, _, _, true) => CompassOctant::East,
+        _ => return None,
+    })
+}
+
+fn is_left_button_pressed() -> bool {
+    // SAFETY: SDL accepts null pointers when the position isn't needed
+    let buttons =
+        unsafe { sdl2::sys::SDL_GetGlobalMouseState(core::ptr::null_mut(), core::ptr::null_mut()) };
+    buttons & (1 << (sdl2::sys::SDL_BUTTON_LEFT - 1)) != 0
+}
+
+/// Installs the hit test callback on a window.
+///
+/// The callback reads `hit_test` so it must be kept alive until [`remove_hit_test`] is called or
+/// the window is destroyed. The callback runs on the SDL thread so this should only be called from
+/// there.
+pub(crate) fn install_hit_test(sdl_window: &sdl2::video::Window, hit_test: &Arc<WindowHitTest>) {
+    // SAFETY: the pointer stays valid as long as the caller keeps `hit_test` alive
+    let result = unsafe {
+        sdl2::sys::SDL_SetWindowHitTest(
+            sdl_window.raw(),
+            Some(hit_test_callback),
+            Arc::as_ptr(hit_test) as *mut c_void,
+        )
+    };
+    if result != 0 {
+        bevy_log::warn!(
+            "Failed to install window hit test, drag move and drag resize won't work: {}",
+            sdl2::get_error()
+        );
+    }
+}
+
+/// Removes the hit test callback of a window.
+pub(crate) fn remove_hit_test(sdl_window: &sdl2::video::Window) {
+    // SAFETY: a null callback disables hit testing
+    unsafe {
+        sdl2::sys::SDL_SetWindowHitTest(sdl_window.raw(), None, core::ptr::null_mut());
+    }
+}
+
+unsafe extern "C" fn hit_test_callback(
+    window: *mut SDL_Window,
+    area: *const SDL_Point,
+    data: *mut c_void,
+) -> SDL_HitTestResult {
+    // SAFETY: `data` is the `WindowHitTest` given to `install_hit_test` which is kept alive until
+    // the callback is removed and `area` is always a valid point
+    let (hit_test, point) = unsafe { (&*(data as *const WindowHitTest), *area) };
+    let (mut width, mut height) = (0, 0);
+    // SAFETY: SDL calls the callback with the window the hit test was installed on
+    unsafe { sdl2::sys::SDL_GetWindowSize(window, &mut width, &mut height) };
+    hit_test
+        .hit_test(
+            Vec2::new(point.x as f32, point.y as f32),
+            Vec2::new(width as f32, height as f32),
+        )
+        .map(HitTestArea::to_sdl)
+        .unwrap_or(SDL_HitTestResult::SDL_HITTEST_NORMAL)
+}
+
+/// The regions need to be scaled again when the scale factor of the window changes, and sent once
+/// the SDL window exists. `Window` changes on every cursor move, so unchanged regions are skipped
+/// by `WindowHitTest::set_regions`.
+type HitTestRegionsChanged = Or<(
+    Changed<Sdl2HitTestRegions>,
+    Changed<Window>,
+    Added<CachedWindow>,
+)>;
+
+/// Sends the [`Sdl2HitTestRegions`] of each window to its hit test callback.
+pub(crate) fn update_hit_test_regions(
+    changed_regions: Query<(Entity, &Window, &Sdl2HitTestRegions), HitTestRegionsChanged>,
+    mut removed_regions: RemovedComponents<Sdl2HitTestRegions>,
+    _non_send_marker: NonSendMarker,
+) {
+    SDL_WINDOWS.with_borrow(|windows| {
+        for entity in removed_regions.read() {
+            if let Some(hit_test) = windows.get_hit_test(entity) {
+                hit_test.set_regions(&Sdl2HitTestRegions::default(), 1.0);
+            }
+        }
+        for (entity, window, regions) in &changed_regions {
+            if let Some(hit_test) = windows.get_hit_test(entity)
+                && let Some(sdl_window) = windows.get_window(entity)
+            {
+                hit_test.set_regions(regions, window.scale_factor() / pixel_ratio(sdl_window));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Vec2 = Vec2::new(400.0, 300.0);
+
+    #[test]
+    fn border_direction_of_edges_and_corners() {
+        let direction = |x, y| border_direction(Vec2::new(x, y), SIZE, 5.0);
+        assert_eq!(direction(0.0, 0.0), Some(CompassOctant::NorthWest));
+        assert_eq!(direction(399.0, 0.0), Some(CompassOctant::NorthEast));
+        assert_eq!(direction(0.0, 299.0), Some(CompassOctant::SouthWest));
+        assert_eq!(direction(399.0, 299.0), Some(CompassOctant::SouthEast));
+        assert_eq!(direction(200.0, 4.0), Some(CompassOctant::North));
+        assert_eq!(direction(200.0, 295.0), Some(CompassOctant::South));
+        assert_eq!(direction(4.0, 150.0), Some(CompassOctant::West));
+        assert_eq!(direction(395.0, 150.0), Some(CompassOctant::East));
+        assert_eq!(direction(5.0, 5.0), None);
+        assert_eq!(direction(394.0, 294.0), None);
+    }
+
+    #[test]
+    fn resize_border_takes_priority_over_regions() {
+        let hit_test = WindowHitTest::default();
+        hit_test.set_regions(
+            &Sdl2HitTestRegions {
+                resize_border: Some(5.0),
+                regions: vec![HitTestRegion {
+                    rect: Rect::new(0.0, 0.0, f32::INFINITY, 30.0),
+                    area: HitTestArea::Draggable,
+                }],
+            },
+            1.0,
+        );
+        assert_eq!(
+            hit_test.hit_test(Vec2::new(200.0, 2.0), SIZE),
+            Some(HitTestArea::Resize(CompassOctant::North))
+        );
+        assert_eq!(
+            hit_test.hit_test(Vec2::new(200.0, 20.0), SIZE),
+            Some(HitTestArea::Draggable)
+        );
+        assert_eq!(hit_test.hit_test(Vec2::new(200.0, 100.0), SIZE), None);
+    }
+
+    #[test]
+    fn regions_are_scaled_to_window_units() {
+        let hit_test = WindowHitTest::default();
+        let regions = Sdl2HitTestRegions {
+            resize_border: None,
+            regions: vec![HitTestRegion {
+                rect: Rect::new(0.0, 0.0, 100.0, 30.0),
+                area: HitTestArea::Draggable,
+            }],
+        };
+        hit_test.set_regions(&regions, 2.0);
+        assert_eq!(
+            hit_test.hit_test(Vec2::new(150.0, 50.0), SIZE),
+            Some(HitTestArea::Draggable)
+        );
+        // Applying the same regions again changes nothing
+        hit_test.set_regions(&regions, 2.0);
+        assert_eq!(
+            hit_test.hit_test(Vec2::new(150.0, 50.0), SIZE),
+            Some(HitTestArea::Draggable)
+        );
+        hit_test.set_regions(&regions, 1.0);
+        assert_eq!(hit_test.hit_test(Vec2::new(150.0, 50.0), SIZE), None);
+    }
+}
@@ -0,0 +1,325 @@
+use core::cell::RefCell;
+
+use bevy_ecs::entity::Entity;
+use bevy_input::{
+    ButtonState,
+    keyboard::{Key, KeyCode, KeyboardFocusLost, KeyboardInput},
+    mouse::{MouseButton, MouseButtonInput},
+};
+use bevy_platform::collections::HashMap;
+use bevy_window::{Window, WindowEvent};
+use sdl2::{keyboard::Mod, rect::Rect};
+
+use crate::scale_factor::pixel_ratio;
+
+thread_local! {
+    static INPUT: RefCell<InputState> = RefCell::new(InputState::default());
+}
+
+/// Keys and mouse buttons currently held down according to the events sent to the app.
+#[derive(Default)]
+struct InputState {
+    keys: HashMap<KeyCode, PressedKey>,
+    /// The window each mouse button was pressed in.
+    buttons: HashMap<MouseButton, Entity>,
+    /// Whether the IME is composing text, the text it commits doesn't belong to a key press.
+    composing: bool,
+    /// Whether SDL will follow the last key press with the text it produced.
+    awaiting_text: bool,
+}
+
+/// A key press sent to the app, its release must be sent too so the key doesn't stay pressed.
+struct PressedKey {
+    /// Reported again on release even if the modifiers changed in between.
+    logical_key: Key,
+    /// Reported on release if the window is gone by then.
+    window: Entity,
+}
+
+/// Whether a key press produces text.
+///
+/// Shortcuts don't produce text, except Ctrl+Alt which is how AltGr is reported on Windows.
+pub(crate) fn produces_text(logical_key: &Key, keymod: Mod) -> bool {
+    let shortcut = Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LGUIMOD | Mod::RGUIMOD;
+    let alt = Mod::LALTMOD | Mod::RALTMOD;
+    matches!(logical_key, Key::Character(_) | Key::Space)
+        && (!keymod.intersects(shortcut) || keymod.intersects(alt))
+}
+
+/// Whether the last event is a key press that SDL will follow with the text it produced.
+///
+/// The press is held back until its text or any other event arrives, even across frames.
+pub(crate) fn expects_key_text(events: &[WindowEvent]) -> bool {
+    let Some(WindowEvent::KeyboardInput(input)) = events.last() else {
+        return false;
+    };
+    input.state == ButtonState::Pressed
+        && input.text.is_none()
+        && INPUT.with_borrow(|state| state.awaiting_text)
+}
+
+/// Attaches text produced by SDL to the key press that produced it.
+///
+/// Returns `false` if the last event isn't a key press of `window` waiting for its text, which
+/// happens when the text comes from an IME.
+pub(crate) fn attach_key_text(events: &mut [WindowEvent], window: Entity, text: &str) -> bool {
+    let Some(WindowEvent::KeyboardInput(input)) = events.last_mut() else {
+        return false;
+    };
+    if input.window != window
+        || input.state != ButtonState::Pressed
+        || input.text.is_some()
+        || INPUT.with_borrow(|state| state.composing)
+    {
+        return false;
+    }
+    INPUT.with_borrow_mut(|state| state.awaiting_text = false);
+    set_key_text(input, text);
+    true
+}
+
+/// Sets the text of a key press, and its logical key if the text replaces its character.
+fn set_key_text(input: &mut KeyboardInput, text: &str) {
+    input.text = Some(text.into());
+    // The text follows the keyboard layout and modifiers, the keycode only follows the layout
+    if let Key::Character(_) = input.logical_key {
+        input.logical_key = Key::Character(text.into());
+        INPUT.with_borrow_mut(|state| {
+            if let Some(pressed) = state.keys.get_mut(&input.key_code) {
+                pressed.logical_key = input.logical_key.clone();
+            }
+        });
+    }
+}
+
+/// Builds the [`KeyboardInput`] of a key press and remembers its logical key.
+///
+/// `awaits_text` tells whether SDL will send the text of the press, see [`produces_text`].
+pub(crate) fn key_pressed(
+    key_code: KeyCode,
+    logical_key: Key,
+    repeat: bool,
+    window: Entity,
+    awaits_text: bool,
+) -> KeyboardInput {
+    INPUT.with_borrow_mut(|state| {
+        state.awaiting_text = awaits_text;
+        state.keys.insert(
+            key_code,
+            PressedKey {
+                logical_key: logical_key.clone(),
+                window,
+            },
+        )
+    });
+    KeyboardInput {
+        key_code,
+        logical_key,
+        state: ButtonState::Pressed,
+        text: None,
+        repeat,
+        window,
+    }
+}
+
+/// Builds the [`KeyboardInput`] of a key release, with the logical key and window of its press.
+///
+/// Returns `None` if the key wasn't pressed in a known window.
+pub(crate) fn key_released(
+    key_code: KeyCode,
+    logical_key: Key,
+    repeat: bool,
+    window: Option<Entity>,
+) -> Option<KeyboardInput> {
+    let (logical_key, window) = match INPUT.with_borrow_mut(|state| state.keys.remove(&key_code)) {
+        Some(pressed) => (pressed.logical_key, window.unwrap_or(pressed.window)),
+        None => (logical_key, window?),
+    };
+    Some(KeyboardInput {
+        key_code,
+        logical_key,
+        state: ButtonState::Released,
+        text: None,
+        repeat,
+        window,
+    })
+}
+
+/// Builds the [`MouseButtonInput`] of a mouse button press and remembers its window.
+pub(crate) fn mouse_button_pressed(button: MouseButton, window: Entity) -> MouseButtonInput {
+    INPUT.with_borrow_mut(|state| state.buttons.insert(button, window));
+    MouseButtonInput {
+        button,
+        state: ButtonState::Pressed,
+        window,
+    }
+}
+
+/// Builds the [`MouseButtonInput`] of a mouse button release, in the window of its press if the
+/// window is gone by then.
+///
+/// Returns `None` if the button wasn't pressed in a known window.
+pub(crate) fn mouse_button_released(
+    button: MouseButton,
+    window: Option<Entity>,
+) -> Option<MouseButtonInput> {
+    let pressed_window = INPUT.with_borrow_mut(|state| state.buttons.remove(&button));
+    Some(MouseButtonInput {
+        button,
+        state: ButtonState::Released,
+        window: window.or(pressed_window)?,
+    })
+}
+
+/// Releases every key and mouse button held when a window loses focus.
+///
+/// Their releases go to the window that has focus next, which may not be ours.
+pub(crate) fn release_held_input(events: &mut Vec<WindowEvent>) {
+    events.push(WindowEvent::KeyboardFocusLost(KeyboardFocusLost));
+    let (keys, buttons) = INPUT.with_borrow_mut(|state| {
+        (
+            core::mem::take(&mut state.keys),
+            core::mem::take(&mut state.buttons),
+        )
+    });
+    events.extend(keys.into_iter().map(|(key_code, pressed)| {
+        WindowEvent::KeyboardInput(KeyboardInput {
+            key_code,
+            logical_key: pressed.logical_key,
+            state: ButtonState::Released,
+            text: None,
+            repeat: false,
+            window: pressed.window,
+        })
+    }));
+    events.extend(buttons.into_iter().map(|(button, window)| {
+        WindowEvent::MouseButtonInput(MouseButtonInput {
+            button,
+            state: ButtonState::Released,
+            window,
+        })
+    }));
+}
+
+/// Presses the modifiers held when a window gains focus, they were pressed in another window.
+pub(crate) fn press_held_modifiers(events: &mut Vec<WindowEvent>, window: Entity) {
+    // The safe keyboard API would clone the SDL context which the SDL thread owns
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    let mod_state = Mod::from_bits_truncate(unsafe { sdl2::sys::SDL_GetModState() } as u16);
+    let modifiers = [
+        (Mod::LSHIFTMOD, KeyCode::ShiftLeft, Key::Shift),
+        (Mod::RSHIFTMOD, KeyCode::ShiftRight, Key::Shift),
+        (Mod::LCTRLMOD, KeyCode::ControlLeft, Key::Control),
+        (Mod::RCTRLMOD, KeyCode::ControlRight, Key::Control),
+        (Mod::LALTMOD, KeyCode::AltLeft, Key::Alt),
+        (Mod::RALTMOD, KeyCode::AltRight, Key::Alt),
+        (Mod::LGUIMOD, KeyCode::SuperLeft, Key::Super),
+        (Mod::RGUIMOD, KeyCode::SuperRight, Key::Super),
+    ];
+    for (modifier, key_code, logical_key) in modifiers {
+        if mod_state.contains(modifier)
+            && !INPUT.with_borrow(|state| state.keys.contains_key(&key_code))
+        {
+            events.push(WindowEvent::KeyboardInput(key_pressed(
+                key_code,
+                logical_key,
+                false,
+                window,
+                false,
+            )));
+        }
+    }
+}
+
+/// Tracks whether the IME is composing text.
+pub(crate) fn set_composing(composing: bool) {
+    INPUT.with_borrow_mut(|state| state.composing = composing);
+}
+
+/// Converts the cursor of an SDL text edit, in characters, to a byte range of its text.
+pub(crate) fn preedit_cursor(text: &str, start: i32, length: i32) -> Option<(usize, usize)> {
+    let byte_index = |chars: i32| {
+        text.char_indices()
+            .map(|(index, _)| index)
+            .chain([text.len()])
+            .nth(usize::try_from(chars).ok()?)
+    };
+    Some((byte_index(start)?, byte_index(start + length.max(0))?))
+}
+
+/// Starts SDL text input for the focused window, and moves the IME candidate window if the IME is
+/// enabled.
+///
+/// Text input is started even without the IME, SDL only sends the text of key presses while it
+/// is. SDL only has one text input state, it follows the focused window.
+pub(crate) fn start_text_input(sdl_window: &sdl2::video::Window, window: &Window) {
+    // The safe text input API clones the video subsystem, whose reference count isn't atomic and
+    // is also changed by the SDL thread
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    unsafe { sdl2::sys::SDL_StartTextInput() };
+    if window.ime_enabled {
+        set_ime_position(sdl_window, window);
+    }
+}
+
+/// Stops SDL text input, which cancels any text the IME was composing.
+pub(crate) fn stop_text_input() {
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    unsafe { sdl2::sys::SDL_StopTextInput() };
+    set_composing(false);
+}
+
+/// Whether SDL text input is started, SDL only sends text while it is.
+pub(crate) fn text_input_active() -> bool {
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    unsafe { sdl2::sys::SDL_IsTextInputActive() == sdl2::sys::SDL_bool::SDL_TRUE }
+}
+
+/// Moves the IME candidate window to [`Window::ime_position`].
+///
+/// SDL only has one text input area, it applies to the focused window.
+pub(crate) fn set_ime_position(sdl_window: &sdl2::video::Window, window: &Window) {
+    let position = (window.ime_position * window.scale_factor() / pixel_ratio(sdl_window))
+        .round()
+        .as_ivec2();
+    let rect = Rect::new(position.x, position.y, 1, 1);
+    // SAFETY: SDL copies the rect before returning
+    unsafe { sdl2::sys::SDL_SetTextInputRect(rect.raw()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_produce_no_text() {
+        let a = Key::Character("a".into());
+        assert!(produces_text(&a, Mod::NOMOD));
+        assert!(produces_text(&a, Mod::LSHIFTMOD));
+        assert!(produces_text(&Key::Space, Mod::NOMOD));
+        assert!(!produces_text(&a, Mod::LCTRLMOD));
+        assert!(!produces_text(&a, Mod::RGUIMOD));
+        assert!(!produces_text(&Key::Enter, Mod::NOMOD));
+    }
+
+    #[test]
+    fn altgr_produces_text() {
+        let a = Key::Character("a".into());
+        assert!(produces_text(&a, Mod::LCTRLMOD | Mod::RALTMOD));
+    }
+
+    #[test]
+    fn preedit_cursor_is_converted_to_bytes() {
+        assert_eq!(preedit_cursor("日本語", 1, 1), Some((3, 6)));
+        assert_eq!(preedit_cursor("日本語", 0, 3), Some((0, 9)));
+        assert_eq!(preedit_cursor("日本語", 3, 0), Some((9, 9)));
+        assert_eq!(preedit_cursor("abc", 2, -1), Some((2, 2)));
+    }
+
+    #[test]
+    fn preedit_cursor_out_of_the_text_is_dropped() {
+        assert_eq!(preedit_cursor("日本語", 4, 0), None);
+        assert_eq!(preedit_cursor("日本語", 2, 2), None);
+        assert_eq!(preedit_cursor("日本語", -1, 1), None);
+    }
+}
@@ -4,29 +4,61 @@ use core::cell::RefCell;
 
 use bevy_app::{PluginsState, prelude::*};
 use bevy_ecs::{
+    message::{MessageCursor, Messages},
     prelude::*,
     system::{NonSendMarker, SystemState},
 };
 use bevy_math::UVec2;
-use crossbeam_channel::Sender;
+use bevy_platform::collections::HashMap;
+use bevy_window::RequestRedraw;
+use crossbeam_channel::{SendError, Sender};
+use sdl2::video::WindowPos;
+use std::{sync::Arc, time::Instant};
 
-use crate::cursor::set_cursor;
+use crate::cursor::{changed_cursor_options, set_cursor};
+use crate::despawn_windows::despawn_windows;
 use crate::frame_limiter::{Sdl2FrameLimiter, Sdl2FrameLimiterPlugin};
-use crate::sdl_windows::SdlWindows;
+use crate::hit_test::{HitTestArea, remove_hit_test, update_hit_test_regions};
+use crate::input::{expects_key_text, set_ime_position};
+use crate::monitors::{
+    Sdl2Monitor, SdlDisplay, enumerate_displays, resolve_window_mode, resolve_window_position,
+    sync_monitors,
+};
+use crate::scale_factor::{pixel_ratio, set_physical_size};
+use crate::sdl_windows::{CursorWarp, SdlWindows, SendSyncSdlWindow};
 use crate::sdl2_event_handler::{HandleEventState, handle_sdl_event};
+use crate::settings::{Sdl2Settings, UpdateMode, current_update_mode};
+use crate::touch::watch_finger_windows;
+use crate::transparency::update_window_opacity;
 use crate::window_event_handler::forward_bevy_window_events;
+use crate::window_icon::update_window_icons;
+use crate::window_mode::{apply_window_mode, display_center, select_display};
 use crate::{
-    create_windows::{CreateWindowParams, WindowReady, build_sdl_window, create_windows},
+    create_windows::{
+        CreateWindowParams, WindowReady, apply_resize_constraints, build_sdl_window,
+        clamp_to_resize_constraints, create_windows,
+    },
     frame_limiter::update_framerate_target,
 };
 
 mod converters;
 mod create_windows;
 mod cursor;
+mod despawn_windows;
 pub mod frame_limiter;
+pub mod hit_test;
+mod input;
+pub mod monitors;
+mod scale_factor;
 mod sdl2_event_handler;
 mod sdl_windows;
+pub mod settings;
+mod touch;
+pub mod transparency;
 mod window_event_handler;
+pub mod window_icon;
+mod window_mode;
+pub mod window_state;
 
 thread_local! {
     pub static SDL_WINDOWS: RefCell<SdlWindows> = const { RefCell::new(SdlWindows::new()) };
@@ -37,11 +69,70 @@ impl Plugin for Sdl2WindowBackendPlugin {
     fn build(&self, app: &mut App) {
         app.set_runner(|app| sdl_runner(app))
             .add_plugins(Sdl2FrameLimiterPlugin)
+            .init_resource::<Sdl2Settings>()
             .add_systems(Last, set_cursor)
-            .add_systems(Last, changed_bevy_windows);
+            .add_systems(Last, changed_cursor_options)
+            .add_systems(Last, changed_bevy_windows)
+            .add_systems(Last, update_hit_test_regions.after(changed_bevy_windows))
+            .add_systems(Last, update_window_icons)
+            .add_systems(Last, update_window_opacity)
+            .add_systems(Last, despawn_windows.after(changed_bevy_windows));
     }
 }
 
+/// Requests sent from the bevy thread to the SDL thread.
+pub(crate) enum SdlRequest {
+    /// Build a new SDL window and send it back once it's ready.
+    CreateWindow(Entity, Box<bevy_window::Window>, Sender<WindowReady>),
+    /// Drop the SDL window on the SDL thread.
+    DestroyWindow(SendSyncSdlWindow),
+}
+
+/// Sends [`SdlRequest`]s to the SDL thread from within bevy systems.
+///
+/// The SDL thread sleeps while waiting for SDL events, so every request also pushes a wake up
+/// event to the SDL event queue.
+#[derive(Resource, Clone)]
+pub(crate) struct SdlRequestSender {
+    sender: Sender<SdlRequest>,
+    waker: Arc<SdlThreadWaker>,
+}
+
+impl SdlRequestSender {
+    pub(crate) fn send(&self, request: SdlRequest) -> Result<(), SendError<SdlRequest>> {
+        self.sender.send(request)?;
+        self.waker.wake();
+        Ok(())
+    }
+}
+
+/// Wakes up the SDL thread by pushing a registered user event to the SDL event queue.
+pub(crate) struct SdlThreadWaker {
+    event_sender: sdl2::event::EventSender,
+    event_type: u32,
+}
+
+impl SdlThreadWaker {
+    fn wake(&self) {
+        let event = sdl2::event::Event::User {
+            timestamp: 0,
+            window_id: 0,
+            type_: self.event_type,
+            code: 0,
+            data1: core::ptr::null_mut(),
+            data2: core::ptr::null_mut(),
+        };
+        if let Err(err) = self.event_sender.push_event(event) {
+            bevy_log::error!("Failed to wake up the SDL thread: {err}");
+        }
+    }
+}
+
+/// How long the SDL thread waits for an event before checking for requests again.
+///
+/// Requests wake up the SDL thread, this is only a safety net in case a wake up event is missed.
+const SDL_THREAD_WAIT_TIMEOUT_MS: u32 = 100;
+
 fn sdl_runner(mut app: App) -> AppExit {
     if app.plugins_state() == PluginsState::Ready {
         app.finish();
@@ -51,17 +142,25 @@ fn sdl_runner(mut app: App) -> AppExit {
     //
     let (sdl_event_sender, sdl_event_receiver) = crossbeam_channel::bounded(1);
 
-    // When creating a window from bevy, we use a channel to send a message to the sdl thread to
-    // build the window. When the window is ready it gets back to the bevy thread
-    let (create_window_sender, create_window_receiver) = crossbeam_channel::unbounded::<(
-        Entity,
-        bevy_window::Window,
-        bevy_window::CursorOptions,
-        Sender<WindowReady>,
-    )>();
+    // When creating or destroying a window from bevy, we use a channel to send a message to the
+    // sdl thread. When a new window is ready it gets back to the bevy thread
+    let (request_sender, sdl_request_receiver) = crossbeam_channel::unbounded::<SdlRequest>();
+
+    // The waker can only be created once SDL is initialized so the SDL thread sends it back
+    let (waker_sender, waker_receiver) = crossbeam_channel::bounded::<SdlThreadWaker>(1);
+
+    // Displays are enumerated on the SDL thread at startup and whenever they change
+    let (display_sender, display_receiver) = crossbeam_channel::unbounded::<Vec<SdlDisplay>>();
 
     // SDL thread
     std::thread::spawn(move || {
+        // Closing the last window should go through bevy's `WindowPlugin` so it can decide
+        // whether or not the app should exit
+        sdl2::hint::set("SDL_QUIT_ON_LAST_WINDOW_CLOSE", "0");
+        // Let Windows scale windows like macOS and Wayland do, the pixel size is read from the
+        // drawable size
+        sdl2::hint::set("SDL_WINDOWS_DPI_SCALING", "1");
+
         let sdl_context = sdl2::init().expect("failed to init sdl");
 
         let Ok(video_subsystem) = sdl_context.video() else {
@@ -78,6 +177,22 @@ fn sdl_runner(mut app: App) -> AppExit {
             bevy_log::error!("failed to get sdl event");
             return AppExit::error();
         };
+        // SAFETY: the event type is only used to push user events without any data attached
+        let Ok(wake_event_type) = (unsafe { event.register_event() }) else {
+            bevy_log::error!("failed to register sdl wake up event");
+            return AppExit::error();
+        };
+        let is_wake_event = |event: &sdl2::event::Event| match event {
+            sdl2::event::Event::User { type_, .. } => *type_ == wake_event_type,
+            _ => false,
+        };
+        let _ = waker_sender.send(SdlThreadWaker {
+            event_sender: event.event_sender(),
+            event_type: wake_event_type,
+        });
+        let _ = display_sender.send(enumerate_displays(&video_subsystem));
+        watch_finger_windows();
+
         let _event_watch = event.add_event_watch(|event| {
             if let sdl2::event::Event::Window { win_event, .. } = event {
                 match win_event {
@@ -95,19 +210,61 @@ fn sdl_runner(mut app: App) -> AppExit {
             }
         });
 
+        let forward_event = |event: sdl2::event::Event| {
+            if is_wake_event(&event) {
+                return;
+            }
+            // Send the new displays first so the monitors are up to date when the event is handled
+            if let sdl2::event::Event::Display { .. } = event {
+                let _ = display_sender.send(enumerate_displays(&video_subsystem));
+            }
+            let _ = sdl_event_sender.send(event);
+        };
+
+        // Keeps the data read by the hit test callbacks alive until their window is destroyed
+        let mut hit_tests = HashMap::new();
+
         loop {
-            while let Ok((_entity, window, cursor_options, ready_sender)) =
-                create_window_receiver.try_recv()
-            {
-                build_sdl_window(&video_subsystem, &window, &cursor_options, ready_sender);
+            // Sleep until SDL or the bevy thread has something for us
+            if let Some(event) = event_pump.wait_event_timeout(SDL_THREAD_WAIT_TIMEOUT_MS) {
+                forward_event(event);
+            }
+            while let Ok(request) = sdl_request_receiver.try_recv() {
+                match request {
+                    SdlRequest::CreateWindow(_entity, window, ready_sender) => {
+                        let (sdl_window_id, hit_test) =
+                            build_sdl_window(&video_subsystem, &window, ready_sender);
+                        hit_tests.insert(sdl_window_id, hit_test);
+                    }
+                    SdlRequest::DestroyWindow(sdl_window) => {
+                        // The window may outlive this request if it's still referenced somewhere
+                        // else, make sure the hit test callback doesn't outlive its data
+                        remove_hit_test(&sdl_window);
+                        hit_tests.remove(&sdl_window.id());
+                        drop(sdl_window);
+                    }
+                }
             }
             for event in event_pump.poll_iter() {
-                let _ = sdl_event_sender.send(event);
+                forward_event(event);
             }
         }
     });
 
+    let Ok(waker) = waker_receiver.recv() else {
+        bevy_log::error!("SDL thread stopped before it was ready");
+        return AppExit::error();
+    };
+    let sdl_request_sender = SdlRequestSender {
+        sender: request_sender,
+        waker: Arc::new(waker),
+    };
+    app.insert_resource(sdl_request_sender.clone());
+
     let mut bevy_window_events: Vec<bevy_window::WindowEvent> = vec![];
+    let mut redraw_message_cursor = MessageCursor::<RequestRedraw>::default();
+    let mut redraw_requested = false;
+    let mut last_update = Instant::now();
     'running: loop {
         // This needs to happen as soon as possible in the frame
         update_framerate_target(&mut app);
@@ -117,24 +274,58 @@ fn sdl_runner(mut app: App) -> AppExit {
             SystemState::<CreateWindowParams<Added<bevy_window::Window>>>::from_world(
                 app.world_mut(),
             );
-        create_windows(
-            create_window.get_mut(app.world_mut()),
-            &create_window_sender,
-        );
+        create_windows(create_window.get_mut(app.world_mut()), &sdl_request_sender);
         create_window.apply(app.world_mut());
 
-        while let Ok(event) = sdl_event_receiver.try_recv() {
+        let update_mode = current_update_mode(app.world_mut());
+        let mut should_update = redraw_requested || update_mode == UpdateMode::Continuous;
+        loop {
+            let event = match update_mode {
+                UpdateMode::Reactive { wait, .. } if !should_update => {
+                    // Sleep until an event arrives or it's time for the next update
+                    match last_update.checked_add(wait) {
+                        Some(deadline) => sdl_event_receiver.recv_deadline(deadline).ok(),
+                        None => sdl_event_receiver.recv().ok(),
+                    }
+                }
+                _ => sdl_event_receiver.try_recv().ok(),
+            };
+            let Some(event) = event else {
+                break;
+            };
+            should_update |= update_mode.reacts_to(&event);
+
             match handle_sdl_event(&mut app, event, &mut bevy_window_events) {
                 HandleEventState::Exit => break 'running,
                 HandleEventState::Continue => {}
             }
 
-            // Forward events
+            // Forward events, unless a key press is waiting for its text
+            if !expects_key_text(&bevy_window_events) {
+                forward_bevy_window_events(
+                    app.world_mut(),
+                    std::mem::take(&mut bevy_window_events),
+                );
+            }
+        }
+        // A key press waiting for its text is held back until the next event, SDL may not have
+        // forwarded the text yet
+        if !expects_key_text(&bevy_window_events) {
             forward_bevy_window_events(app.world_mut(), std::mem::take(&mut bevy_window_events));
         }
 
+        if let Some(displays) = display_receiver.try_iter().last() {
+            sync_monitors(app.world_mut(), displays);
+        }
+
+        last_update = Instant::now();
         app.update();
 
+        redraw_requested = app
+            .world()
+            .get_resource::<Messages<RequestRedraw>>()
+            .is_some_and(|messages| redraw_message_cursor.read(messages).last().is_some());
+
         if app.should_exit().is_some() {
             break 'running;
         }
@@ -146,7 +337,7 @@ fn sdl_runner(mut app: App) -> AppExit {
     AppExit::Success
 }
 
-/// The cached state of the window so we can check which properties were changed from within the app.
+/// The cached state of the window, to check which properties were changed from within the app.
 #[derive(Debug, Clone, Component)]
 pub(crate) struct CachedWindow(bevy_window::Window);
 
@@ -155,18 +346,52 @@ fn changed_bevy_windows(
         (Entity, &mut bevy_window::Window, &mut CachedWindow),
         Changed<bevy_window::Window>,
     >,
+    monitors: Query<&Sdl2Monitor>,
+    mut ime_messages: MessageWriter<bevy_window::Ime>,
     _non_send_marker: NonSendMarker,
 ) {
     SDL_WINDOWS.with_borrow_mut(|windows| {
-        for (entity, window, mut cache) in &mut changed_windows {
-            let Some(sdl_window) = windows
-                .entity_to_sdl_window
-                .get(&entity)
-                .and_then(|window_id| windows.windows.get_mut(window_id))
-            else {
+        for (entity, mut window, mut cache) in &mut changed_windows {
+            let hit_test = windows.get_hit_test(entity).cloned();
+            let Some(&window_id) = windows.entity_to_sdl_window.get(&entity) else {
+                continue;
+            };
+            let Some(sdl_window) = windows.windows.get_mut(&window_id) else {
                 continue;
             };
 
+            // The click that requested the drag was already handled by the OS, so the drag starts
+            // on the next click in the window, see `WindowHitTest`
+            if window.internal.take_move_request()
+                && let Some(hit_test) = &hit_test
+            {
+                hit_test.arm(HitTestArea::Draggable);
+            }
+            if let Some(direction) = window.internal.take_resize_request()
+                && let Some(hit_test) = &hit_test
+            {
+                hit_test.arm(HitTestArea::Resize(direction));
+            }
+
+            if let Some(minimized) = window.internal.take_minimize_request() {
+                if minimized {
+                    sdl_window.minimize();
+                } else {
+                    sdl_window.restore();
+                }
+            }
+            if let Some(maximized) = window.internal.take_maximize_request() {
+                if maximized {
+                    sdl_window.maximize();
+                } else {
+                    sdl_window.restore();
+                }
+            }
+            // Windows can't be unfocused on request, the OS decides which window gets focus next
+            if window.focused && !cache.0.focused {
+                sdl_window.raise();
+            }
+
             if window.title != cache.0.title {
                 if let Err(_) = sdl_window.set_title(&window.title) {
                     bevy_log::error!("Failed to set window title");
@@ -182,6 +407,87 @@ fn changed_bevy_windows(
             if window.resizable != cache.0.resizable {
                 sdl_window.set_resizable(window.resizable);
             }
+            if window.decorations != cache.0.decorations {
+                sdl_window.set_bordered(window.decorations);
+            }
+            if window.window_level != cache.0.window_level {
+                match window.window_level {
+                    bevy_window::WindowLevel::AlwaysOnBottom => {
+                        bevy_log::warn_once!(
+                            "WindowLevel::AlwaysOnBottom is not supported by SDL2"
+                        );
+                        sdl_window.set_always_on_top(false);
+                    }
+                    bevy_window::WindowLevel::Normal => sdl_window.set_always_on_top(false),
+                    bevy_window::WindowLevel::AlwaysOnTop => sdl_window.set_always_on_top(true),
+                }
+            }
+            if window.skip_taskbar != cache.0.skip_taskbar {
+                bevy_log::warn_once!("skip_taskbar can only be set when the window is created");
+            }
+            if window.mode != cache.0.mode {
+                apply_window_mode(sdl_window, &resolve_window_mode(window.mode, &monitors));
+            }
+            if window.position != cache.0.position {
+                match resolve_window_position(window.position, &monitors) {
+                    bevy_window::WindowPosition::Automatic => {}
+                    bevy_window::WindowPosition::Centered(monitor) => {
+                        let (width, height) = sdl_window.size();
+                        if let Some((x, y)) = select_display(
+                            sdl_window.subsystem(),
+                            sdl_window.display_index().ok(),
+                            &monitor,
+                        )
+                        .and_then(|display| {
+                            display_center(sdl_window.subsystem(), display, width, height)
+                        }) {
+                            sdl_window
+                                .set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+                        }
+                    }
+                    bevy_window::WindowPosition::At(position) => {
+                        sdl_window.set_position(
+                            WindowPos::Positioned(position.x),
+                            WindowPos::Positioned(position.y),
+                        );
+                    }
+                }
+            }
+            if window.ime_enabled != cache.0.ime_enabled {
+                ime_messages.write(if window.ime_enabled {
+                    bevy_window::Ime::Enabled { window: entity }
+                } else {
+                    bevy_window::Ime::Disabled { window: entity }
+                });
+            }
+            // Text input itself stays started, it also provides the text of key presses
+            if window.focused
+                && window.ime_enabled
+                && (window.ime_enabled != cache.0.ime_enabled
+                    || window.ime_position != cache.0.ime_position)
+            {
+                set_ime_position(sdl_window, &window);
+            }
+            // Cursor moves reported by SDL are cached, so this was set from within the app
+            if window.physical_cursor_position() != cache.0.physical_cursor_position()
+                && let Some(position) = window.physical_cursor_position()
+            {
+                let position = (position / pixel_ratio(sdl_window)).round().as_ivec2();
+                // SAFETY: the window is alive, the safe mouse API would clone the SDL context which
+                // the SDL thread owns
+                unsafe {
+                    sdl2::sys::SDL_WarpMouseInWindow(sdl_window.raw(), position.x, position.y)
+                };
+                windows
+                    .cursor_warps
+                    .insert(window_id, CursorWarp::new((position.x, position.y)));
+            }
+            if window.resize_constraints != cache.0.resize_constraints
+                || window.scale_factor() != cache.0.scale_factor()
+            {
+                apply_resize_constraints(sdl_window, &window);
+                clamp_to_resize_constraints(&mut window);
+            }
             if window.resolution != cache.0.resolution {
                 let mut physical_size = UVec2::new(
                     window.resolution.physical_width(),
@@ -219,9 +525,7 @@ fn changed_bevy_windows(
                 }
 
                 if physical_size != cached_physical_size {
-                    if let Err(_) = sdl_window.set_size(physical_size.x, physical_size.y) {
-                        bevy_log::error!("Failed to set window size");
-                    }
+                    set_physical_size(sdl_window, physical_size);
                 }
             }
             *cache = CachedWindow(window.clone());
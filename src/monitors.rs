@@ -0,0 +1,185 @@
+use bevy_ecs::{component::Component, entity::Entity, query::With, system::Query, world::World};
+use bevy_math::{IRect, IVec2};
+use bevy_window::{
+    Monitor, MonitorSelection, PrimaryMonitor, VideoMode, WindowMode, WindowPosition,
+};
+use sdl2::VideoSubsystem;
+
+use crate::{converters::convert_sdl_display_mode, scale_factor::dpi_scale_factor};
+
+/// SDL specific information about a [`Monitor`] entity.
+///
+/// Like [`Monitor`], this is synchronized with SDL and should be treated as read-only.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Sdl2Monitor {
+    /// Index of the SDL display.
+    ///
+    /// Indices can change when displays are connected or disconnected.
+    pub index: i32,
+    /// The area of the monitor that isn't covered by task bars or docks, in the same coordinates
+    /// as [`Monitor::physical_position`].
+    pub usable_area: IRect,
+    /// The diagonal DPI of the monitor, if SDL knows it.
+    pub dpi: Option<f32>,
+}
+
+/// A display enumerated on the SDL thread, sent to the bevy thread to update monitor entities.
+pub(crate) struct SdlDisplay {
+    monitor: Monitor,
+    sdl_monitor: Sdl2Monitor,
+}
+
+/// Reads every display currently known by SDL.
+pub(crate) fn enumerate_displays(video_subsystem: &VideoSubsystem) -> Vec<SdlDisplay> {
+    let num_displays = match video_subsystem.num_video_displays() {
+        Ok(num_displays) => num_displays,
+        Err(err) => {
+            bevy_log::error!("Failed to enumerate displays: {err}");
+            return Vec::new();
+        }
+    };
+    (0..num_displays)
+        .filter_map(|index| match read_display(video_subsystem, index) {
+            Ok(display) => Some(display),
+            Err(err) => {
+                bevy_log::error!("Failed to read display {index}: {err}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn read_display(video_subsystem: &VideoSubsystem, index: i32) -> Result<SdlDisplay, String> {
+    let bounds = video_subsystem.display_bounds(index)?;
+    let usable_bounds = video_subsystem.display_usable_bounds(index)?;
+    let current_mode = video_subsystem.current_display_mode(index).ok();
+    let (ddpi, hdpi) = match video_subsystem.display_dpi(index) {
+        Ok((ddpi, hdpi, _)) => (Some(ddpi), Some(hdpi)),
+        Err(_) => (None, None),
+    };
+    let video_modes: Vec<VideoMode> = (0..video_subsystem.num_display_modes(index)?)
+        .filter_map(|mode_index| video_subsystem.display_mode(index, mode_index).ok())
+        .map(|display_mode| convert_sdl_display_mode(&display_mode))
+        .collect();
+
+    let monitor = Monitor {
+        name: video_subsystem.display_name(index).ok(),
+        // The display mode is in pixels, the bounds can be in points on some platforms
+        physical_width: current_mode.map_or(bounds.width(), |mode| mode.w as u32),
+        physical_height: current_mode.map_or(bounds.height(), |mode| mode.h as u32),
+        physical_position: IVec2::new(bounds.x(), bounds.y()),
+        refresh_rate_millihertz: current_mode
+            .filter(|mode| mode.refresh_rate > 0)
+            .map(|mode| mode.refresh_rate as u32 * 1000),
+        scale_factor: hdpi.map_or(1.0, |hdpi| dpi_scale_factor(hdpi) as f64),
+        video_modes,
+    };
+    let sdl_monitor = Sdl2Monitor {
+        index,
+        usable_area: IRect::new(
+            usable_bounds.x(),
+            usable_bounds.y(),
+            usable_bounds.x() + usable_bounds.width() as i32,
+            usable_bounds.y() + usable_bounds.height() as i32,
+        ),
+        dpi: ddpi,
+    };
+    Ok(SdlDisplay {
+        monitor,
+        sdl_monitor,
+    })
+}
+
+/// Spawns, updates and despawns [`Monitor`] entities to match the displays enumerated by SDL.
+///
+/// SDL2 has no stable display identifier, so existing entities are matched by name and position.
+pub(crate) fn sync_monitors(world: &mut World, displays: Vec<SdlDisplay>) {
+    let mut unmatched: Vec<(Entity, Monitor)> = world
+        .query_filtered::<(Entity, &Monitor), With<Sdl2Monitor>>()
+        .iter(world)
+        .map(|(entity, monitor)| (entity, monitor.clone()))
+        .collect();
+
+    for display in displays {
+        let matched = unmatched
+            .iter()
+            .position(|(_, monitor)| {
+                monitor.name == display.monitor.name
+                    && monitor.physical_position == display.monitor.physical_position
+            })
+            .or_else(|| {
+                unmatched
+                    .iter()
+                    .position(|(_, monitor)| monitor.name == display.monitor.name)
+            });
+        // SDL always reports the primary display first
+        let primary = display.sdl_monitor.index == 0;
+        let mut entity = match matched {
+            Some(matched) => {
+                let mut entity = world.entity_mut(unmatched.swap_remove(matched).0);
+                entity.insert((display.monitor, display.sdl_monitor));
+                entity
+            }
+            None => {
+                let entity = world.spawn((display.monitor, display.sdl_monitor));
+                bevy_log::info!("Monitor connected {}", entity.id());
+                entity
+            }
+        };
+        if primary {
+            entity.insert(PrimaryMonitor);
+        } else {
+            entity.remove::<PrimaryMonitor>();
+        }
+    }
+
+    for (entity, _) in unmatched {
+        bevy_log::info!("Monitor disconnected {entity}");
+        world.despawn(entity);
+    }
+}
+
+/// Replaces [`MonitorSelection::Entity`] with the index of the SDL display of that monitor.
+///
+/// Displays are selected on the SDL thread which can't access monitor entities.
+pub(crate) fn resolve_monitor(
+    selection: MonitorSelection,
+    monitors: &Query<&Sdl2Monitor>,
+) -> MonitorSelection {
+    match selection {
+        MonitorSelection::Entity(entity) => match monitors.get(entity) {
+            Ok(monitor) => MonitorSelection::Index(monitor.index as usize),
+            Err(_) => {
+                bevy_log::warn!("{entity} is not a monitor, using the current monitor instead");
+                MonitorSelection::Current
+            }
+        },
+        selection => selection,
+    }
+}
+
+/// Resolves the [`MonitorSelection`] of a [`WindowMode`], see [`resolve_monitor`].
+pub(crate) fn resolve_window_mode(mode: WindowMode, monitors: &Query<&Sdl2Monitor>) -> WindowMode {
+    match mode {
+        WindowMode::Windowed => WindowMode::Windowed,
+        WindowMode::BorderlessFullscreen(monitor) => {
+            WindowMode::BorderlessFullscreen(resolve_monitor(monitor, monitors))
+        }
+        WindowMode::Fullscreen(monitor, video_mode) => {
+            WindowMode::Fullscreen(resolve_monitor(monitor, monitors), video_mode)
+        }
+    }
+}
+
+/// Resolves the [`MonitorSelection`] of a [`WindowPosition`], see [`resolve_monitor`].
+pub(crate) fn resolve_window_position(
+    position: WindowPosition,
+    monitors: &Query<&Sdl2Monitor>,
+) -> WindowPosition {
+    match position {
+        WindowPosition::Centered(monitor) => {
+            WindowPosition::Centered(resolve_monitor(monitor, monitors))
+        }
+        position => position,
+    }
+}
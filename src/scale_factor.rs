@@ -0,0 +1,73 @@
+use bevy_math::UVec2;
+
+/// The DPI of a display with a scale factor of 1.
+#[cfg(target_os = "macos")]
+const BASE_DPI: f32 = 72.0;
+#[cfg(not(target_os = "macos"))]
+const BASE_DPI: f32 = 96.0;
+
+/// Returns the number of physical pixels per SDL window unit.
+///
+/// SDL measures windows in screen coordinates, which are scaled by the OS on macOS, Wayland and
+/// Windows (with `SDL_WINDOWS_DPI_SCALING`). The drawable size is always in pixels.
+pub(crate) fn pixel_ratio(sdl_window: &sdl2::video::Window) -> f32 {
+    let (width, _) = sdl_window.size();
+    let (drawable_width, _) = sdl_window.drawable_size();
+    if width == 0 || drawable_width == 0 {
+        return 1.0;
+    }
+    drawable_width as f32 / width as f32
+}
+
+/// Returns the scale factor the OS suggests for a window.
+pub(crate) fn base_scale_factor(sdl_window: &sdl2::video::Window) -> f32 {
+    let pixel_ratio = pixel_ratio(sdl_window);
+    if pixel_ratio != 1.0 || cfg!(target_os = "macos") {
+        return pixel_ratio;
+    }
+    // X11 doesn't scale windows so the DPI of the display is the only hint
+    sdl_window
+        .display_index()
+        .and_then(|display| sdl_window.subsystem().display_dpi(display))
+        .map_or(1.0, |(_, hdpi, _)| dpi_scale_factor(hdpi))
+}
+
+/// Returns the scale factor matching the horizontal DPI of a display.
+///
+/// Displays below the base DPI keep a scale factor of 1, their DPI is usually misreported. The
+/// factor is rounded to a twelfth like winit does, a DPI slightly off the base would otherwise
+/// blur the whole UI.
+pub(crate) fn dpi_scale_factor(hdpi: f32) -> f32 {
+    ((hdpi / BASE_DPI * 12.0).round() / 12.0).max(1.0)
+}
+
+/// Resizes an SDL window to a size in physical pixels.
+pub(crate) fn set_physical_size(sdl_window: &mut sdl2::video::Window, physical: UVec2) {
+    let size = (physical.as_vec2() / pixel_ratio(sdl_window))
+        .round()
+        .as_uvec2();
+    if sdl_window.size() == (size.x, size.y) {
+        return;
+    }
+    if let Err(err) = sdl_window.set_size(size.x, size.y) {
+        bevy_log::error!("Failed to set window size: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dpi_scale_factor_is_rounded_to_a_twelfth() {
+        assert_eq!(dpi_scale_factor(BASE_DPI), 1.0);
+        assert_eq!(dpi_scale_factor(BASE_DPI * 1.03), 1.0);
+        assert_eq!(dpi_scale_factor(BASE_DPI * 1.49), 1.5);
+        assert_eq!(dpi_scale_factor(BASE_DPI * 2.0), 2.0);
+    }
+
+    #[test]
+    fn low_dpi_displays_are_not_scaled_down() {
+        assert_eq!(dpi_scale_factor(BASE_DPI / 2.0), 1.0);
+    }
+}
@@ -1,6 +1,13 @@
-use crate::converters::{convert_sdl_keycode, convert_sdl_scancode, convert_sdl_touch_event};
+use crate::converters::convert_sdl_key;
+use crate::input::{
+    attach_key_text, key_pressed, key_released, mouse_button_pressed, mouse_button_released,
+    preedit_cursor, press_held_modifiers, produces_text, release_held_input, set_composing,
+    text_input_active,
+};
+use crate::scale_factor::pixel_ratio;
+use crate::touch::{cancel_touches, convert_finger_event, convert_multi_gesture};
 use crate::window_event_handler::{HandleSdlWindowEventParams, handle_sdl_window_event};
-use crate::{SDL_WINDOWS, converters::convert_sdl_mouse_btn};
+use crate::{CachedWindow, SDL_WINDOWS, converters::convert_sdl_mouse_btn};
 use bevy_app::App;
 use bevy_ecs::{system::SystemState, world::FromWorld};
 use bevy_log::error;
@@ -26,13 +33,26 @@ pub(crate) fn handle_sdl_event(
             win_event,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                if let Some(entity) = windows.get_window_entity(window_id) {
+            SDL_WINDOWS.with_borrow_mut(|windows| {
+                if let Some(entity) = windows.get_window_entity(window_id)
+                    && let Some(sdl_window) = windows.windows.get_mut(&window_id)
+                {
+                    match win_event {
+                        sdl2::event::WindowEvent::FocusLost => {
+                            release_held_input(bevy_window_events);
+                            cancel_touches(bevy_window_events, entity);
+                        }
+                        sdl2::event::WindowEvent::FocusGained => {
+                            press_held_modifiers(bevy_window_events, entity);
+                        }
+                        _ => {}
+                    }
                     let mut window_event_state =
                         SystemState::<HandleSdlWindowEventParams>::from_world(app.world_mut());
                     handle_sdl_window_event(
                         window_event_state.get_mut(app.world_mut()),
                         entity,
+                        sdl_window,
                         win_event,
                     );
                 }
@@ -42,29 +62,20 @@ pub(crate) fn handle_sdl_event(
             window_id,
             keycode,
             scancode,
+            keymod,
             repeat,
             ..
         } => {
             SDL_WINDOWS.with_borrow(|windows| {
-                let entity = windows
-                    .get_window_entity(window_id)
-                    .expect("Window entity not found");
-                let Some(key_code) = scancode.and_then(convert_sdl_scancode) else {
-                    return;
-                };
-                let Some(logical_key) = keycode.and_then(convert_sdl_keycode) else {
+                let Some(entity) = windows.get_window_entity(window_id) else {
                     return;
                 };
-                bevy_window_events.push(bevy_window::WindowEvent::KeyboardInput(
-                    bevy_input::keyboard::KeyboardInput {
-                        key_code,
-                        logical_key,
-                        state: bevy_input::ButtonState::Pressed,
-                        text: None,
-                        repeat,
-                        window: entity,
-                    },
-                ));
+                let (key_code, logical_key) = convert_sdl_key(scancode, keycode);
+                // SDL only sends text while text input is started, which follows window focus
+                let awaits_text = produces_text(&logical_key, keymod) && text_input_active();
+                let input = key_pressed(key_code, logical_key, repeat, entity, awaits_text);
+                // The text is attached when SDL sends it
+                bevy_window_events.push(bevy_window::WindowEvent::KeyboardInput(input));
             });
         }
         Event::KeyUp {
@@ -74,27 +85,12 @@ pub(crate) fn handle_sdl_event(
             repeat,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                let entity = windows
-                    .get_window_entity(window_id)
-                    .expect("Window entity not found");
-                let Some(key_code) = scancode.and_then(convert_sdl_scancode) else {
-                    return;
-                };
-                let Some(logical_key) = keycode.and_then(convert_sdl_keycode) else {
-                    return;
-                };
-                bevy_window_events.push(bevy_window::WindowEvent::KeyboardInput(
-                    bevy_input::keyboard::KeyboardInput {
-                        key_code,
-                        logical_key,
-                        state: bevy_input::ButtonState::Released,
-                        text: None,
-                        repeat,
-                        window: entity,
-                    },
-                ));
-            });
+            let (key_code, logical_key) = convert_sdl_key(scancode, keycode);
+            // The window may have been closed while the key was held
+            let entity = SDL_WINDOWS.with_borrow(|windows| windows.get_window_entity(window_id));
+            if let Some(input) = key_released(key_code, logical_key, repeat, entity) {
+                bevy_window_events.push(bevy_window::WindowEvent::KeyboardInput(input));
+            }
         }
         Event::MouseButtonDown {
             window_id,
@@ -102,19 +98,15 @@ pub(crate) fn handle_sdl_event(
             ..
         } => {
             SDL_WINDOWS.with_borrow(|windows| {
-                let entity = windows
-                    .get_window_entity(window_id)
-                    .expect("Window entity not found");
+                let Some(entity) = windows.get_window_entity(window_id) else {
+                    return;
+                };
                 let Some(button) = convert_sdl_mouse_btn(mouse_btn) else {
                     error!("Unknown mouse button: {:?}", mouse_btn);
                     return;
                 };
                 bevy_window_events.push(bevy_window::WindowEvent::MouseButtonInput(
-                    bevy_input::mouse::MouseButtonInput {
-                        button,
-                        state: bevy_input::ButtonState::Pressed,
-                        window: entity,
-                    },
+                    mouse_button_pressed(button, entity),
                 ));
             });
         }
@@ -123,52 +115,72 @@ pub(crate) fn handle_sdl_event(
             mouse_btn,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                let entity = windows
-                    .get_window_entity(window_id)
-                    .expect("Window entity not found");
-                let Some(button) = convert_sdl_mouse_btn(mouse_btn) else {
-                    error!("Unknown mouse button: {:?}", mouse_btn);
-                    return;
-                };
-                bevy_window_events.push(bevy_window::WindowEvent::MouseButtonInput(
-                    bevy_input::mouse::MouseButtonInput {
-                        button,
-                        state: bevy_input::ButtonState::Released,
-                        window: entity,
-                    },
-                ));
-            });
+            let Some(button) = convert_sdl_mouse_btn(mouse_btn) else {
+                error!("Unknown mouse button: {:?}", mouse_btn);
+                return HandleEventState::Continue;
+            };
+            // The window may have been closed while the button was held
+            let entity = SDL_WINDOWS.with_borrow(|windows| windows.get_window_entity(window_id));
+            if let Some(input) = mouse_button_released(button, entity) {
+                bevy_window_events.push(bevy_window::WindowEvent::MouseButtonInput(input));
+            }
         }
         Event::MouseMotion {
             window_id,
+            mousestate,
             x,
             y,
             xrel,
             yrel,
             ..
         } => {
-            bevy_window_events.push(bevy_window::WindowEvent::MouseMotion(
-                bevy_input::mouse::MouseMotion {
-                    delta: Vec2::new(xrel as f32, yrel as f32),
-                },
-            ));
+            // Warping the cursor moves it by the distance to the new position, which isn't a
+            // mouse movement
+            let warped =
+                SDL_WINDOWS.with_borrow_mut(|windows| windows.take_cursor_warp(window_id, (x, y)));
+            if !warped {
+                bevy_window_events.push(bevy_window::WindowEvent::MouseMotion(
+                    bevy_input::mouse::MouseMotion {
+                        delta: Vec2::new(xrel as f32, yrel as f32),
+                    },
+                ));
+            }
             SDL_WINDOWS.with_borrow(|windows| {
                 let Some(entity) = windows.get_window_entity(window_id) else {
                     return;
                 };
-                let Some(mut win) = app.world_mut().get_mut::<bevy_window::Window>(entity) else {
+                // A drag request is only used by a click that follows it directly
+                if mousestate.to_sdl_state() == 0
+                    && let Some(hit_test) = windows.get_hit_test(entity)
+                {
+                    hit_test.disarm();
+                }
+                // The entity may be gone before `despawn_windows` forgets its SDL window
+                let Ok(mut entity_mut) = app.world_mut().get_entity_mut(entity) else {
+                    return;
+                };
+                let Some(mut win) = entity_mut.get_mut::<bevy_window::Window>() else {
                     return;
                 };
-                let physical_position = DVec2::new(x as f64, y as f64);
+                // SDL reports the position in window units
+                let pixel_ratio = windows
+                    .get_window(entity)
+                    .map_or(1.0, |sdl_window| pixel_ratio(sdl_window));
+                let physical_position = DVec2::new(x as f64, y as f64) * pixel_ratio as f64;
 
+                let scale_factor = win.resolution.scale_factor();
                 let last_position = win.physical_cursor_position();
-                let delta = last_position.map(|last_pos| {
-                    (physical_position.as_vec2() - last_pos) / win.resolution.scale_factor()
-                });
+                let delta = last_position
+                    .map(|last_pos| (physical_position.as_vec2() - last_pos) / scale_factor);
 
                 win.set_physical_cursor_position(Some(physical_position));
-                let position = (physical_position / win.resolution.scale_factor() as f64).as_vec2();
+                let position = (physical_position / scale_factor as f64).as_vec2();
+                // Only cursor moves made from within the app should warp the cursor
+                if let Some(mut cache) = entity_mut.get_mut::<CachedWindow>() {
+                    cache
+                        .0
+                        .set_physical_cursor_position(Some(physical_position));
+                }
                 bevy_window_events.push(bevy_window::WindowEvent::CursorMoved(
                     bevy_window::CursorMoved {
                         delta,
@@ -185,9 +197,9 @@ pub(crate) fn handle_sdl_event(
             ..
         } => {
             SDL_WINDOWS.with_borrow(|windows| {
-                let entity = windows
-                    .get_window_entity(window_id)
-                    .expect("Window entity not found");
+                let Some(entity) = windows.get_window_entity(window_id) else {
+                    return;
+                };
                 bevy_window_events.push(bevy_window::WindowEvent::MouseWheel(
                     bevy_input::mouse::MouseWheel {
                         unit: bevy_input::mouse::MouseScrollUnit::Line,
@@ -207,6 +219,22 @@ pub(crate) fn handle_sdl_event(
                 let Some(entity) = windows.get_window_entity(window_id) else {
                     return;
                 };
+                // Text typed on the keyboard belongs to its key press, the rest comes from the IME
+                if attach_key_text(bevy_window_events, entity, text) {
+                    return;
+                }
+                set_composing(false);
+                // Text that doesn't match a key press is committed even without the IME so it
+                // isn't lost
+                if ime_enabled(app, entity) {
+                    bevy_window_events.push(bevy_window::WindowEvent::Ime(
+                        bevy_window::Ime::Preedit {
+                            window: entity,
+                            value: String::new(),
+                            cursor: None,
+                        },
+                    ));
+                }
                 bevy_window_events.push(bevy_window::WindowEvent::Ime(bevy_window::Ime::Commit {
                     window: entity,
                     value: text.clone(),
@@ -224,10 +252,14 @@ pub(crate) fn handle_sdl_event(
                 let Some(entity) = windows.get_window_entity(window_id) else {
                     return;
                 };
+                set_composing(!text.is_empty());
+                if !ime_enabled(app, entity) {
+                    return;
+                }
                 bevy_window_events.push(bevy_window::WindowEvent::Ime(bevy_window::Ime::Preedit {
                     window: entity,
                     value: text.clone(),
-                    cursor: Some((start as usize, (start + length) as usize)),
+                    cursor: preedit_cursor(text, start, length).filter(|_| !text.is_empty()),
                 }));
             });
         }
@@ -255,21 +287,16 @@ pub(crate) fn handle_sdl_event(
             pressure,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                let Some(entity) = windows.get_window_entity(0) else {
-                    return;
-                };
-                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(
-                    convert_sdl_touch_event(
-                        bevy_input::touch::TouchPhase::Started,
-                        finger_id,
-                        x,
-                        y,
-                        pressure,
-                        entity,
-                    ),
-                ));
-            });
+            if let Some(touch) = convert_finger_event(
+                app.world(),
+                bevy_input::touch::TouchPhase::Started,
+                finger_id,
+                x,
+                y,
+                pressure,
+            ) {
+                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(touch));
+            }
         }
         Event::FingerUp {
             finger_id,
@@ -278,21 +305,16 @@ pub(crate) fn handle_sdl_event(
             pressure,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                let Some(entity) = windows.get_window_entity(0) else {
-                    return;
-                };
-                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(
-                    convert_sdl_touch_event(
-                        bevy_input::touch::TouchPhase::Ended,
-                        finger_id,
-                        x,
-                        y,
-                        pressure,
-                        entity,
-                    ),
-                ));
-            });
+            if let Some(touch) = convert_finger_event(
+                app.world(),
+                bevy_input::touch::TouchPhase::Ended,
+                finger_id,
+                x,
+                y,
+                pressure,
+            ) {
+                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(touch));
+            }
         }
         Event::FingerMotion {
             finger_id,
@@ -301,21 +323,33 @@ pub(crate) fn handle_sdl_event(
             pressure,
             ..
         } => {
-            SDL_WINDOWS.with_borrow(|windows| {
-                let Some(entity) = windows.get_window_entity(0) else {
-                    return;
-                };
-                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(
-                    convert_sdl_touch_event(
-                        bevy_input::touch::TouchPhase::Moved,
-                        finger_id,
-                        x,
-                        y,
-                        pressure,
-                        entity,
-                    ),
-                ));
-            });
+            if let Some(touch) = convert_finger_event(
+                app.world(),
+                bevy_input::touch::TouchPhase::Moved,
+                finger_id,
+                x,
+                y,
+                pressure,
+            ) {
+                bevy_window_events.push(bevy_window::WindowEvent::TouchInput(touch));
+            }
+        }
+        Event::MultiGesture {
+            d_theta,
+            d_dist,
+            x,
+            y,
+            num_fingers,
+            ..
+        } => {
+            convert_multi_gesture(
+                app.world(),
+                bevy_window_events,
+                d_theta,
+                d_dist,
+                Vec2::new(x, y),
+                num_fingers,
+            );
         }
         _ => {
             // dbg!(e);
@@ -323,3 +357,13 @@ pub(crate) fn handle_sdl_event(
     }
     HandleEventState::Continue
 }
+
+/// Whether the app wants IME events for a window.
+///
+/// SDL text input stays started for the focused window regardless, since it also provides the
+/// text of key presses.
+fn ime_enabled(app: &App, entity: bevy_ecs::entity::Entity) -> bool {
+    app.world()
+        .get::<bevy_window::Window>(entity)
+        .is_some_and(|window| window.ime_enabled)
+}
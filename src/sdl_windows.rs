@@ -1,9 +1,12 @@
+use crate::hit_test::WindowHitTest;
 use bevy_derive::{Deref, DerefMut};
 use bevy_ecs::entity::{Entity, EntityHashMap};
 use bevy_platform::collections::HashMap;
 use raw_window_handle::{
-    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, WindowHandle,
+    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, RawDisplayHandle,
+    RawWindowHandle, WindowHandle,
 };
+use std::sync::Arc;
 
 /// Newtype over `sdl2::video::Window` that is `Send + Sync`.
 ///
@@ -42,6 +45,72 @@ impl HasDisplayHandle for SendSyncSdlWindow {
     }
 }
 
+/// The raw handles of an SDL window, without a reference to the window itself.
+///
+/// This is what the render world gets, so the last reference to an SDL window is always dropped on
+/// the SDL thread and its reference count is never touched from render threads.
+///
+/// # Safety
+///
+/// The handles are only valid until the window is destroyed, [`WindowClosing`] is written a frame
+/// before that so the render world can drop its surface.
+///
+/// [`WindowClosing`]: bevy_window::WindowClosing
+#[derive(Clone, Copy)]
+pub(crate) struct SdlRawHandles {
+    window: RawWindowHandle,
+    display: RawDisplayHandle,
+}
+
+impl SdlRawHandles {
+    pub(crate) fn new(sdl_window: &sdl2::video::Window) -> Result<Self, HandleError> {
+        Ok(SdlRawHandles {
+            window: sdl_window.window_handle()?.as_raw(),
+            display: sdl_window.display_handle()?.as_raw(),
+        })
+    }
+}
+
+// SAFETY: the handles are plain pointers, see the type-level doc above.
+unsafe impl Send for SdlRawHandles {}
+unsafe impl Sync for SdlRawHandles {}
+
+impl HasWindowHandle for SdlRawHandles {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        // SAFETY: the window outlives the handles, see the type-level doc above
+        Ok(unsafe { WindowHandle::borrow_raw(self.window) })
+    }
+}
+
+impl HasDisplayHandle for SdlRawHandles {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        // SAFETY: the display connection outlives the window
+        Ok(unsafe { DisplayHandle::borrow_raw(self.display) })
+    }
+}
+
+/// Mouse motion events that can pass before a cursor warp is assumed to have been merged with
+/// another motion by SDL.
+const CURSOR_WARP_EVENTS: u8 = 4;
+
+/// A cursor position the app warped to, waiting for its mouse motion event.
+#[derive(Clone, Copy)]
+pub(crate) struct CursorWarp {
+    /// The position in SDL window units.
+    position: (i32, i32),
+    remaining_events: u8,
+}
+
+impl CursorWarp {
+    /// A warp to `position`, in SDL window units.
+    pub(crate) fn new(position: (i32, i32)) -> Self {
+        CursorWarp {
+            position,
+            remaining_events: CURSOR_WARP_EVENTS,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct SdlWindows {
     /// SDL windows keyed by SDL window ID.
@@ -50,6 +119,10 @@ pub struct SdlWindows {
     pub entity_to_sdl_window: EntityHashMap<u32>,
     /// Maps SDL window ID to Bevy entity.
     pub sdl_window_to_entity: HashMap<u32, Entity>,
+    /// Hit test state keyed by SDL window ID.
+    pub(crate) hit_tests: HashMap<u32, Arc<WindowHitTest>>,
+    /// Cursor positions the app warped to keyed by SDL window ID, in SDL window units.
+    pub(crate) cursor_warps: HashMap<u32, CursorWarp>,
     // Opt out of Send + Sync so this type is confined to one thread.
     _not_send_sync: core::marker::PhantomData<*const ()>,
 }
@@ -60,6 +133,8 @@ impl SdlWindows {
             windows: HashMap::new(),
             entity_to_sdl_window: EntityHashMap::new(),
             sdl_window_to_entity: HashMap::new(),
+            hit_tests: HashMap::new(),
+            cursor_warps: HashMap::new(),
             _not_send_sync: core::marker::PhantomData,
         }
     }
@@ -71,8 +146,50 @@ impl SdlWindows {
             .and_then(|id| self.windows.get(id))
     }
 
+    /// Get the SDL window associated with a Bevy entity mutably.
+    pub fn get_window_mut(&mut self, entity: Entity) -> Option<&mut SendSyncSdlWindow> {
+        self.entity_to_sdl_window
+            .get(&entity)
+            .and_then(|id| self.windows.get_mut(id))
+    }
+
     /// Get the Bevy entity associated with an SDL window ID.
     pub fn get_window_entity(&self, sdl_window_id: u32) -> Option<Entity> {
         self.sdl_window_to_entity.get(&sdl_window_id).cloned()
     }
+
+    /// Get the hit test state of the SDL window associated with a Bevy entity.
+    pub(crate) fn get_hit_test(&self, entity: Entity) -> Option<&Arc<WindowHitTest>> {
+        self.entity_to_sdl_window
+            .get(&entity)
+            .and_then(|id| self.hit_tests.get(id))
+    }
+
+    /// Returns whether a mouse motion to `position` was caused by a cursor warp and consumes it.
+    ///
+    /// Warps whose motion doesn't arrive within a few events are forgotten, so a later motion to
+    /// the same position isn't mistaken for one.
+    pub(crate) fn take_cursor_warp(&mut self, sdl_window_id: u32, position: (i32, i32)) -> bool {
+        let Some(warp) = self.cursor_warps.get_mut(&sdl_window_id) else {
+            return false;
+        };
+        let warped = warp.position == position;
+        warp.remaining_events -= 1;
+        if warped || warp.remaining_events == 0 {
+            self.cursor_warps.remove(&sdl_window_id);
+        }
+        warped
+    }
+
+    /// Remove the SDL window associated with a Bevy entity and return it.
+    ///
+    /// The window is only destroyed once the returned value (and every other clone of it) is
+    /// dropped.
+    pub fn remove_window(&mut self, entity: Entity) -> Option<SendSyncSdlWindow> {
+        let sdl_window_id = self.entity_to_sdl_window.remove(&entity)?;
+        self.sdl_window_to_entity.remove(&sdl_window_id);
+        self.hit_tests.remove(&sdl_window_id);
+        self.cursor_warps.remove(&sdl_window_id);
+        self.windows.remove(&sdl_window_id)
+    }
 }
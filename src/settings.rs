@@ -0,0 +1,288 @@
+use std::time::Duration;
+
+use bevy_ecs::{resource::Resource, world::World};
+
+use crate::{window_event_handler::is_occluded, window_state::Sdl2WindowState};
+
+/// Settings for the SDL2 runner.
+///
+/// Controls how often the app is updated depending on whether one of its windows is focused.
+#[derive(Debug, Resource, Clone)]
+pub struct Sdl2Settings {
+    /// Determines how frequently the app updates when one of its windows is focused.
+    pub focused_mode: UpdateMode,
+    /// Determines how frequently the app updates when none of its windows are focused.
+    pub unfocused_mode: UpdateMode,
+    /// Determines how frequently the app updates when all of its windows are hidden or minimized.
+    ///
+    /// `None` uses [`focused_mode`](Self::focused_mode) or [`unfocused_mode`](Self::unfocused_mode)
+    /// like when the windows are visible.
+    pub occluded_mode: Option<UpdateMode>,
+}
+
+impl Sdl2Settings {
+    /// Default settings for games.
+    ///
+    /// [`Continuous`](UpdateMode::Continuous) if focused, and
+    /// [`reactive_low_power`](UpdateMode::reactive_low_power) at 60 fps if not.
+    pub fn game() -> Self {
+        Sdl2Settings {
+            focused_mode: UpdateMode::Continuous,
+            unfocused_mode: UpdateMode::reactive_low_power(Duration::from_secs_f64(1.0 / 60.0)),
+            occluded_mode: None,
+        }
+    }
+
+    /// Default settings for desktop applications.
+    ///
+    /// [`Reactive`](UpdateMode::Reactive) if focused, and
+    /// [`reactive_low_power`](UpdateMode::reactive_low_power) if not.
+    pub fn desktop_app() -> Self {
+        Sdl2Settings {
+            focused_mode: UpdateMode::reactive(Duration::from_secs(5)),
+            unfocused_mode: UpdateMode::reactive_low_power(Duration::from_secs(60)),
+            occluded_mode: None,
+        }
+    }
+
+    /// Updates the app as fast as possible, whether it's focused or not.
+    pub fn continuous() -> Self {
+        Sdl2Settings {
+            focused_mode: UpdateMode::Continuous,
+            unfocused_mode: UpdateMode::Continuous,
+            occluded_mode: None,
+        }
+    }
+
+    /// Returns the current [`UpdateMode`].
+    pub fn update_mode(&self, focused: bool) -> UpdateMode {
+        if focused {
+            self.focused_mode
+        } else {
+            self.unfocused_mode
+        }
+    }
+}
+
+impl Default for Sdl2Settings {
+    fn default() -> Self {
+        Sdl2Settings::game()
+    }
+}
+
+/// Determines how frequently an app should update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// The app will update over and over, as fast as it can, until an `AppExit` message is
+    /// written.
+    Continuous,
+    /// The app will update in response to the following, until an `AppExit` message is written:
+    /// - `wait` time has elapsed since the previous update
+    /// - a redraw has been requested with [`RequestRedraw`](bevy_window::RequestRedraw)
+    /// - new window or device events have arrived
+    ///
+    /// Until then, the runner sleeps.
+    Reactive {
+        /// The approximate time from the start of one update to the next.
+        ///
+        /// **Note:** This has no upper limit.
+        /// The app will wait indefinitely if you set this to [`Duration::MAX`].
+        wait: Duration,
+        /// Reacts to SDL events that are not tied to a window, like display or device changes.
+        react_to_device_events: bool,
+        /// Reacts to SDL events that are tied to a window, like keyboard and mouse input, focus
+        /// or resizing.
+        react_to_window_events: bool,
+    },
+}
+
+impl UpdateMode {
+    /// Reactive mode, will update the app for any kind of event
+    pub fn reactive(wait: Duration) -> Self {
+        Self::Reactive {
+            wait,
+            react_to_device_events: true,
+            react_to_window_events: true,
+        }
+    }
+
+    /// Low power mode
+    ///
+    /// Unlike [`Reactive`](`UpdateMode::reactive()`), this will ignore events that
+    /// don't come from interacting with a window, like display or device changes.
+    pub fn reactive_low_power(wait: Duration) -> Self {
+        Self::Reactive {
+            wait,
+            react_to_device_events: false,
+            react_to_window_events: true,
+        }
+    }
+
+    /// Whether an SDL event should wake up the runner and trigger an update.
+    pub(crate) fn reacts_to(&self, event: &sdl2::event::Event) -> bool {
+        match self {
+            UpdateMode::Continuous => true,
+            UpdateMode::Reactive {
+                react_to_device_events,
+                react_to_window_events,
+                ..
+            } => {
+                // Touches have no window ID but are routed to the window under them
+                let window_event = event.get_window_id().is_some()
+                    || matches!(
+                        event,
+                        sdl2::event::Event::FingerDown { .. }
+                            | sdl2::event::Event::FingerUp { .. }
+                            | sdl2::event::Event::FingerMotion { .. }
+                            | sdl2::event::Event::MultiGesture { .. }
+                    );
+                if window_event {
+                    *react_to_window_events
+                } else {
+                    *react_to_device_events
+                }
+            }
+        }
+    }
+}
+
+/// Returns the [`UpdateMode`] the runner should use for the next update.
+pub(crate) fn current_update_mode(world: &mut World) -> UpdateMode {
+    let mut windows = world.query::<(&bevy_window::Window, Option<&Sdl2WindowState>)>();
+    let focused = windows.iter(world).any(|(window, _)| window.focused);
+    let occluded = windows.iter(world).next().is_some()
+        && windows
+            .iter(world)
+            .all(|(window, state)| state.is_some_and(|state| is_occluded(window, state)));
+    let Some(settings) = world.get_resource::<Sdl2Settings>() else {
+        return UpdateMode::Continuous;
+    };
+    match settings.occluded_mode {
+        Some(occluded_mode) if occluded => occluded_mode,
+        _ => settings.update_mode(focused),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OCCLUDED: UpdateMode = UpdateMode::Reactive {
+        wait: Duration::from_secs(1),
+        react_to_device_events: false,
+        react_to_window_events: false,
+    };
+
+    fn world_with_settings() -> World {
+        let mut world = World::new();
+        world.insert_resource(Sdl2Settings {
+            occluded_mode: Some(OCCLUDED),
+            ..Sdl2Settings::desktop_app()
+        });
+        world
+    }
+
+    fn window(focused: bool, visible: bool) -> bevy_window::Window {
+        bevy_window::Window {
+            focused,
+            visible,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn continuous_without_settings() {
+        let mut world = World::new();
+        world.spawn((window(false, false), Sdl2WindowState::default()));
+        assert_eq!(current_update_mode(&mut world), UpdateMode::Continuous);
+    }
+
+    #[test]
+    fn follows_focus() {
+        let settings = Sdl2Settings::desktop_app();
+        let mut world = World::new();
+        world.insert_resource(settings.clone());
+        let entity = world
+            .spawn((window(true, true), Sdl2WindowState::default()))
+            .id();
+        assert_eq!(current_update_mode(&mut world), settings.focused_mode);
+        world.entity_mut(entity).insert(window(false, true));
+        assert_eq!(current_update_mode(&mut world), settings.unfocused_mode);
+    }
+
+    #[test]
+    fn occluded_when_every_window_is_hidden_or_minimized() {
+        let mut world = world_with_settings();
+        world.spawn((window(false, false), Sdl2WindowState::default()));
+        let minimized = Sdl2WindowState {
+            minimized: true,
+            maximized: false,
+        };
+        let entity = world.spawn((window(true, true), minimized)).id();
+        assert_eq!(current_update_mode(&mut world), OCCLUDED);
+        world.entity_mut(entity).insert(Sdl2WindowState::default());
+        assert_eq!(
+            current_update_mode(&mut world),
+            Sdl2Settings::desktop_app().focused_mode
+        );
+    }
+
+    #[test]
+    fn windows_not_created_yet_are_not_occluded() {
+        let mut world = world_with_settings();
+        world.spawn(window(false, false));
+        assert_eq!(
+            current_update_mode(&mut world),
+            Sdl2Settings::desktop_app().unfocused_mode
+        );
+    }
+
+    #[test]
+    fn no_windows_is_not_occluded() {
+        let mut world = world_with_settings();
+        assert_eq!(
+            current_update_mode(&mut world),
+            Sdl2Settings::desktop_app().unfocused_mode
+        );
+    }
+
+    #[test]
+    fn low_power_reacts_to_window_and_touch_events() {
+        let mode = UpdateMode::reactive_low_power(Duration::from_secs(1));
+        let finger_down = sdl2::event::Event::FingerDown {
+            timestamp: 0,
+            touch_id: 0,
+            finger_id: 0,
+            x: 0.5,
+            y: 0.5,
+            dx: 0.0,
+            dy: 0.0,
+            pressure: 1.0,
+        };
+        let gesture = sdl2::event::Event::MultiGesture {
+            timestamp: 0,
+            touch_id: 0,
+            d_theta: 0.1,
+            d_dist: 0.0,
+            x: 0.5,
+            y: 0.5,
+            num_fingers: 2,
+        };
+        let key_down = sdl2::event::Event::KeyDown {
+            timestamp: 0,
+            window_id: 1,
+            keycode: None,
+            scancode: None,
+            keymod: sdl2::keyboard::Mod::NOMOD,
+            repeat: false,
+        };
+        let device_added = sdl2::event::Event::JoyDeviceAdded {
+            timestamp: 0,
+            which: 0,
+        };
+        assert!(mode.reacts_to(&finger_down));
+        assert!(mode.reacts_to(&gesture));
+        assert!(mode.reacts_to(&key_down));
+        assert!(!mode.reacts_to(&device_added));
+    }
+}
@@ -0,0 +1,327 @@
+use core::{
+    cell::RefCell,
+    ffi::{c_int, c_void},
+};
+use std::sync::Mutex;
+
+use bevy_ecs::{entity::Entity, world::World};
+use bevy_input::{
+    gestures::{PanGesture, PinchGesture, RotationGesture},
+    touch::{TouchInput, TouchPhase},
+};
+use bevy_math::Vec2;
+use bevy_platform::collections::HashMap;
+use bevy_window::{Window, WindowEvent};
+use sdl2::sys::{SDL_Event, SDL_EventType};
+
+use crate::{SDL_WINDOWS, converters::convert_sdl_touch_event};
+
+/// The SDL window ID each finger touched, keyed by finger ID.
+///
+/// SDL sets the window of finger events but the `sdl2` crate doesn't expose it, so it's recorded
+/// by an event watch on the SDL thread.
+static FINGER_WINDOWS: Mutex<HashMap<i64, u32>> = Mutex::new(HashMap::new());
+
+thread_local! {
+    /// The window and last logical position of each finger touching one of our windows.
+    static TOUCHES: RefCell<HashMap<i64, (Entity, Vec2)>> = const { RefCell::new(HashMap::new()) };
+    /// The touches before the last finger motion, the gestures SDL sends after it are relative to
+    /// them.
+    static GESTURE_BASE: RefCell<Option<GestureBase>> = const { RefCell::new(None) };
+}
+
+/// The state of the touches of a window before one of its fingers moved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GestureBase {
+    /// The window of the moved finger.
+    window: Entity,
+    /// The center of the fingers in logical pixels.
+    center: Vec2,
+    /// The distance between the moved finger and the center, normalized to the window size like
+    /// SDL gesture distances.
+    finger_distance: f32,
+}
+
+impl GestureBase {
+    /// Computes the base of a motion of the finger at `finger_position` from the positions of all
+    /// the fingers touching its window, including that one.
+    fn new(window: Entity, positions: &[Vec2], finger_position: Vec2, size: Vec2) -> Option<Self> {
+        if positions.is_empty() || size.cmple(Vec2::ZERO).any() {
+            return None;
+        }
+        let center = positions.iter().sum::<Vec2>() / positions.len() as f32;
+        Some(GestureBase {
+            window,
+            center,
+            finger_distance: ((finger_position - center) / size).length(),
+        })
+    }
+
+    /// Converts the distance change of an SDL gesture to a [`PinchGesture`] delta, the relative
+    /// change of the distance between the fingers and their center.
+    fn pinch(&self, d_dist: f32) -> Option<f32> {
+        (self.finger_distance > 0.0).then(|| d_dist / self.finger_distance)
+    }
+
+    /// Converts the center of an SDL gesture, normalized to the window, to a [`PanGesture`] delta
+    /// in logical pixels.
+    fn pan(&self, center: Vec2, size: Vec2) -> Vec2 {
+        center * size - self.center
+    }
+}
+
+/// Converts the angle change of an SDL gesture, in radians, to a [`RotationGesture`] delta in
+/// degrees.
+fn rotation(d_theta: f32) -> f32 {
+    // SDL angles grow clockwise since the Y axis points down
+    -d_theta.to_degrees()
+}
+
+/// Starts recording the window of each finger, must be called on the SDL thread.
+pub(crate) fn watch_finger_windows() {
+    // SAFETY: the callback doesn't use its user data and lives as long as the program
+    unsafe { sdl2::sys::SDL_AddEventWatch(Some(record_finger_window), core::ptr::null_mut()) };
+}
+
+unsafe extern "C" fn record_finger_window(_data: *mut c_void, event: *mut SDL_Event) -> c_int {
+    // SAFETY: SDL calls the watch with a valid event, `tfinger` is only read for finger events
+    let finger = unsafe {
+        if (*event).type_ != SDL_EventType::SDL_FINGERDOWN as u32 {
+            return 0;
+        }
+        (*event).tfinger
+    };
+    if finger.windowID != 0 {
+        FINGER_WINDOWS
+            .lock()
+            .unwrap()
+            .insert(finger.fingerId, finger.windowID);
+    }
+    0
+}
+
+/// Converts an SDL finger event to a [`TouchInput`] of the window under the finger.
+///
+/// SDL positions are normalized to the window. Touches stay in the window they started in, fingers
+/// that didn't start in one of our windows are ignored.
+pub(crate) fn convert_finger_event(
+    world: &World,
+    phase: TouchPhase,
+    finger_id: i64,
+    x: f32,
+    y: f32,
+    pressure: f32,
+) -> Option<TouchInput> {
+    let window = match phase {
+        TouchPhase::Started => {
+            let recorded_window = FINGER_WINDOWS.lock().unwrap().remove(&finger_id);
+            let window_id = recorded_window.or_else(keyboard_focus_window_id)?;
+            SDL_WINDOWS.with_borrow(|windows| windows.get_window_entity(window_id))?
+        }
+        _ => TOUCHES.with_borrow(|touches| touches.get(&finger_id).map(|(window, _)| *window))?,
+    };
+    let size = world.get::<Window>(window)?.size();
+    let position = Vec2::new(x, y) * size;
+    TOUCHES.with_borrow_mut(|touches| match phase {
+        TouchPhase::Moved => {
+            // SDL processes gestures after queuing the motion, so they arrive after it
+            let finger_position = touches.get(&finger_id).map(|&(_, position)| position);
+            let positions: Vec<Vec2> = touches
+                .values()
+                .filter(|&&(touch_window, _)| touch_window == window)
+                .map(|&(_, position)| position)
+                .collect();
+            GESTURE_BASE.set(
+                finger_position
+                    .and_then(|finger| GestureBase::new(window, &positions, finger, size)),
+            );
+            touches.insert(finger_id, (window, position));
+        }
+        TouchPhase::Started => {
+            touches.insert(finger_id, (window, position));
+        }
+        TouchPhase::Ended | TouchPhase::Canceled => {
+            touches.remove(&finger_id);
+        }
+    });
+    Some(convert_sdl_touch_event(
+        phase, finger_id, position, pressure, window,
+    ))
+}
+
+/// Cancels the touches of a window that lost focus, their end may never be reported to it.
+pub(crate) fn cancel_touches(events: &mut Vec<WindowEvent>, window: Entity) {
+    TOUCHES.with_borrow_mut(|touches| {
+        touches.retain(|&finger_id, &mut (touch_window, position)| {
+            if touch_window != window {
+                return true;
+            }
+            events.push(WindowEvent::TouchInput(convert_sdl_touch_event(
+                TouchPhase::Canceled,
+                finger_id,
+                position,
+                0.0,
+                window,
+            )));
+            false
+        });
+    });
+}
+
+/// Returns the ID of the window with keyboard focus, touching a window usually focuses it.
+fn keyboard_focus_window_id() -> Option<u32> {
+    // SAFETY: SDL is initialized as long as one of its windows exists
+    let sdl_window = unsafe { sdl2::sys::SDL_GetKeyboardFocus() };
+    if sdl_window.is_null() {
+        return None;
+    }
+    // SAFETY: the window was just returned by SDL
+    Some(unsafe { sdl2::sys::SDL_GetWindowID(sdl_window) })
+}
+
+/// Converts an SDL multi finger gesture to the gestures recognized by the window of the fingers
+/// that produced it.
+///
+/// SDL sends a gesture right after the finger motion that caused it, its deltas are relative to
+/// the touches before that motion.
+pub(crate) fn convert_multi_gesture(
+    world: &World,
+    events: &mut Vec<WindowEvent>,
+    d_theta: f32,
+    d_dist: f32,
+    center: Vec2,
+    num_fingers: u16,
+) {
+    let Some(base) = GESTURE_BASE.take() else {
+        return;
+    };
+    let Some(window) = world.get::<Window>(base.window) else {
+        return;
+    };
+
+    if window.recognize_pinch_gesture
+        && let Some(delta) = base.pinch(d_dist)
+    {
+        events.push(WindowEvent::PinchGesture(PinchGesture(delta)));
+    }
+    if window.recognize_rotation_gesture {
+        events.push(WindowEvent::RotationGesture(RotationGesture(rotation(
+            d_theta,
+        ))));
+    }
+    if let Some((min_fingers, max_fingers)) = window.recognize_pan_gesture
+        && (min_fingers as u16..=max_fingers as u16).contains(&num_fingers)
+    {
+        events.push(WindowEvent::PanGesture(PanGesture(
+            base.pan(center, window.size()),
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_window::WindowResolution;
+
+    const SIZE: Vec2 = Vec2::new(800.0, 600.0);
+
+    /// A world with a window of 800x600 physical pixels and a scale factor of 2.
+    fn world_with_window() -> (World, Entity) {
+        let mut world = World::new();
+        let window = world
+            .spawn(Window {
+                resolution: WindowResolution::new(800, 600).with_scale_factor_override(2.0),
+                ..Default::default()
+            })
+            .id();
+        (world, window)
+    }
+
+    #[test]
+    fn touches_start_in_the_window_under_the_finger() {
+        let (world, window) = world_with_window();
+        FINGER_WINDOWS.lock().unwrap().insert(1, 7);
+        SDL_WINDOWS.with_borrow_mut(|windows| windows.sdl_window_to_entity.insert(7, window));
+        let touch = convert_finger_event(&world, TouchPhase::Started, 1, 0.5, 0.25, 0.0).unwrap();
+        assert_eq!(touch.window, window);
+        // Logical coordinates
+        assert_eq!(touch.position, Vec2::new(200.0, 75.0));
+    }
+
+    #[test]
+    fn touches_stay_in_the_window_they_started_in() {
+        let (world, window) = world_with_window();
+        TOUCHES.with_borrow_mut(|touches| touches.insert(2, (window, Vec2::ZERO)));
+        let touch = convert_finger_event(&world, TouchPhase::Moved, 2, 1.0, 1.0, 0.0).unwrap();
+        assert_eq!(touch.window, window);
+        assert_eq!(touch.position, Vec2::new(400.0, 300.0));
+        convert_finger_event(&world, TouchPhase::Ended, 2, 1.0, 1.0, 0.0).unwrap();
+        assert!(convert_finger_event(&world, TouchPhase::Moved, 2, 1.0, 1.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn fingers_outside_our_windows_are_ignored() {
+        let (world, _) = world_with_window();
+        assert!(convert_finger_event(&world, TouchPhase::Moved, 3, 0.5, 0.5, 0.0).is_none());
+    }
+
+    fn base(positions: &[Vec2], finger: Vec2) -> GestureBase {
+        GestureBase::new(Entity::PLACEHOLDER, positions, finger, SIZE).unwrap()
+    }
+
+    #[test]
+    fn pan_is_the_center_change_in_logical_pixels() {
+        let base = base(
+            &[Vec2::new(200.0, 300.0), Vec2::new(600.0, 300.0)],
+            Vec2::new(600.0, 300.0),
+        );
+        assert_eq!(base.center, Vec2::new(400.0, 300.0));
+        // The right finger moved 80 pixels right, moving the center by 40
+        let center = Vec2::new(440.0, 300.0) / SIZE;
+        assert!(
+            base.pan(center, SIZE)
+                .abs_diff_eq(Vec2::new(40.0, 0.0), 1e-3)
+        );
+        assert!(
+            base.pan(base.center / SIZE, SIZE)
+                .abs_diff_eq(Vec2::ZERO, 1e-3)
+        );
+    }
+
+    #[test]
+    fn pinch_is_relative_to_the_previous_finger_distance() {
+        let base = base(
+            &[Vec2::new(200.0, 300.0), Vec2::new(600.0, 300.0)],
+            Vec2::new(600.0, 300.0),
+        );
+        // 200 pixels from the center, 0.25 of the window width
+        assert!((base.finger_distance - 0.25).abs() < 1e-6);
+        assert!((base.pinch(0.05).unwrap() - 0.2).abs() < 1e-6);
+        assert!((base.pinch(-0.125).unwrap() + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn pinch_is_skipped_for_a_finger_on_the_center() {
+        let base = base(&[Vec2::new(400.0, 300.0)], Vec2::new(400.0, 300.0));
+        assert_eq!(base.pinch(0.1), None);
+    }
+
+    #[test]
+    fn no_base_without_touches_or_size() {
+        assert_eq!(
+            GestureBase::new(Entity::PLACEHOLDER, &[], Vec2::ZERO, SIZE),
+            None
+        );
+        let positions = [Vec2::ZERO, Vec2::ONE];
+        assert_eq!(
+            GestureBase::new(Entity::PLACEHOLDER, &positions, Vec2::ONE, Vec2::ZERO),
+            None
+        );
+    }
+
+    #[test]
+    fn rotation_is_counterclockwise_in_degrees() {
+        assert!((rotation(core::f32::consts::FRAC_PI_2) + 90.0).abs() < 1e-4);
+        assert!((rotation(-core::f32::consts::PI) - 180.0).abs() < 1e-4);
+    }
+}
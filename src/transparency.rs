@@ -0,0 +1,124 @@
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    lifecycle::RemovedComponents,
+    query::{Added, Changed, Or},
+    system::{NonSendMarker, Query},
+};
+use bevy_window::CompositeAlphaMode;
+use sdl2::VideoSubsystem;
+
+use crate::{CachedWindow, SDL_WINDOWS};
+
+/// The opacity of a whole window, including its decorations.
+///
+/// Add this component to a [`Window`](bevy_window::Window) entity to fade it in or out. `1.0` is
+/// fully opaque and `0.0` fully transparent. For windows with transparent content, use
+/// [`Window::transparent`](bevy_window::Window::transparent) instead.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Sdl2WindowOpacity(pub f32);
+
+impl Default for Sdl2WindowOpacity {
+    fn default() -> Self {
+        Sdl2WindowOpacity(1.0)
+    }
+}
+
+/// Sets the SDL hints needed to create a window with or without an alpha channel.
+///
+/// This must be called on the SDL thread right before building the window.
+pub(crate) fn prepare_window_visual(
+    video_subsystem: &VideoSubsystem,
+    window: &bevy_window::Window,
+) {
+    let transparent = window.transparent;
+    // The surface Bevy renders to is created by wgpu, not SDL, so its alpha mode decides whether
+    // the compositor blends the window
+    if transparent
+        && matches!(
+            window.composite_alpha_mode,
+            CompositeAlphaMode::Auto | CompositeAlphaMode::Opaque
+        )
+    {
+        bevy_log::warn_once!(
+            "Transparent windows need a composite_alpha_mode other than Auto or Opaque, like \
+             PostMultiplied"
+        );
+    }
+    match video_subsystem.current_video_driver() {
+        "x11" => {
+            // X11 windows only have an alpha channel when created with a 32 bit visual
+            let visual_id = transparent.then(x11_argb_visual_id).flatten();
+            if transparent && visual_id.is_none() {
+                bevy_log::warn_once!("No 32 bit visual available, transparent windows won't work");
+            }
+            sdl2::hint::set(
+                "SDL_VIDEO_X11_WINDOW_VISUALID",
+                visual_id.as_deref().unwrap_or(""),
+            );
+        }
+        // Wayland surfaces are transparent as soon as their content has alpha
+        "wayland" => {}
+        driver => {
+            if transparent {
+                bevy_log::warn_once!(
+                    "Transparent windows are not supported with the {driver} driver"
+                );
+            }
+        }
+    }
+}
+
+/// Returns the ID of a 32 bit visual of the default X11 screen.
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "android")))]
+fn x11_argb_visual_id() -> Option<String> {
+    use x11_dl::xlib::{TrueColor, XVisualInfo, Xlib};
+
+    let xlib = Xlib::open().ok()?;
+    // SAFETY: the display is checked before use and closed before returning, SDL opens its own
+    // connection to the same server so the visual ID stays valid
+    unsafe {
+        let display = (xlib.XOpenDisplay)(core::ptr::null());
+        if display.is_null() {
+            return None;
+        }
+        let screen = (xlib.XDefaultScreen)(display);
+        let mut visual_info = core::mem::MaybeUninit::<XVisualInfo>::zeroed();
+        let found =
+            (xlib.XMatchVisualInfo)(display, screen, 32, TrueColor, visual_info.as_mut_ptr());
+        (xlib.XCloseDisplay)(display);
+        (found != 0).then(|| visual_info.assume_init().visualid.to_string())
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"), not(target_os = "android"))))]
+fn x11_argb_visual_id() -> Option<String> {
+    None
+}
+
+/// The opacity needs to be applied again once the SDL window exists.
+type OpacityChanged = Or<(Changed<Sdl2WindowOpacity>, Added<CachedWindow>)>;
+
+/// Applies the [`Sdl2WindowOpacity`] of each window to its SDL window.
+pub(crate) fn update_window_opacity(
+    changed_opacity: Query<(Entity, &Sdl2WindowOpacity), OpacityChanged>,
+    mut removed_opacity: RemovedComponents<Sdl2WindowOpacity>,
+    _non_send_marker: NonSendMarker,
+) {
+    SDL_WINDOWS.with_borrow_mut(|windows| {
+        let removed = removed_opacity
+            .read()
+            .map(|entity| (entity, Sdl2WindowOpacity::default()));
+        let changed = changed_opacity
+            .iter()
+            .map(|(entity, opacity)| (entity, *opacity));
+        for (entity, Sdl2WindowOpacity(opacity)) in removed.chain(changed) {
+            let Some(sdl_window) = windows.get_window_mut(entity) else {
+                continue;
+            };
+            if let Err(err) = sdl_window.set_opacity(opacity.clamp(0.0, 1.0)) {
+                bevy_log::error!("Failed to set window opacity: {err}");
+            }
+        }
+    });
+}
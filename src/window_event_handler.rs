@@ -1,6 +1,11 @@
 use crate::CachedWindow;
+use crate::create_windows::apply_resize_constraints;
+use crate::cursor::apply_focused_cursor_options;
+use crate::input::{start_text_input, stop_text_input};
+use crate::scale_factor::base_scale_factor;
+use crate::window_state::Sdl2WindowState;
 use bevy_ecs::{
-    change_detection::DetectChanges,
+    change_detection::{DetectChanges, DetectChangesMut},
     entity::Entity,
     message::{MessageWriter, Messages},
     system::{Query, SystemParamItem},
@@ -9,13 +14,25 @@ use bevy_ecs::{
 use bevy_log::warn;
 
 pub type HandleSdlWindowEventParams<'w, 's> = (
-    Query<'w, 's, (&'static mut bevy_window::Window, &'static mut CachedWindow)>,
+    Query<
+        'w,
+        's,
+        (
+            &'static mut bevy_window::Window,
+            &'static mut CachedWindow,
+            &'static mut Sdl2WindowState,
+            Option<&'static bevy_window::CursorOptions>,
+        ),
+    >,
     MessageWriter<'w, bevy_window::WindowResized>,
     MessageWriter<'w, bevy_window::WindowMoved>,
     MessageWriter<'w, bevy_window::CursorEntered>,
     MessageWriter<'w, bevy_window::CursorLeft>,
     MessageWriter<'w, bevy_window::WindowFocused>,
     MessageWriter<'w, bevy_window::WindowCloseRequested>,
+    MessageWriter<'w, bevy_window::WindowBackendScaleFactorChanged>,
+    MessageWriter<'w, bevy_window::WindowScaleFactorChanged>,
+    MessageWriter<'w, bevy_window::WindowOccluded>,
 );
 
 pub fn handle_sdl_window_event(
@@ -27,31 +44,62 @@ pub fn handle_sdl_window_event(
         mut cursor_left,
         mut window_focused,
         mut window_close_requested,
+        mut backend_scale_factor_changed,
+        mut scale_factor_changed,
+        mut window_occluded,
     ): SystemParamItem<HandleSdlWindowEventParams>,
     entity: Entity,
+    sdl_window: &mut sdl2::video::Window,
     win_event: sdl2::event::WindowEvent,
 ) {
     use sdl2::event::WindowEvent as SdlWindowEvent;
-    let (mut window, _) = query.get_mut(entity).expect("failed to get Window");
+    // The window may have been despawned while SDL still had events queued for it
+    let Ok((mut window, _, mut state, cursor_options)) = query.get_mut(entity) else {
+        return;
+    };
+    let was_occluded = is_occluded(&window, &state);
     match win_event {
         SdlWindowEvent::Exposed | SdlWindowEvent::Resized(_, _) => {
             // repaint here
         }
         // SdlWindowEvent::Resized(width, height) |
-        SdlWindowEvent::SizeChanged(width, height) => {
-            window
-                .resolution
-                .set_physical_resolution(width as u32, height as u32);
-            window_resized.write(bevy_window::WindowResized {
-                window: entity,
-                width: window.width(),
-                height: window.height(),
-            });
+        SdlWindowEvent::SizeChanged(_, _) | SdlWindowEvent::DisplayChanged(_) => {
+            // The window may have moved to a display with a different scale factor
+            let scale_factor = base_scale_factor(sdl_window);
+            let rescaled = scale_factor != window.resolution.base_scale_factor();
+            if rescaled {
+                window.resolution.set_scale_factor(scale_factor);
+                backend_scale_factor_changed.write(bevy_window::WindowBackendScaleFactorChanged {
+                    window: entity,
+                    scale_factor: scale_factor as f64,
+                });
+                if window.resolution.scale_factor_override().is_none() {
+                    scale_factor_changed.write(bevy_window::WindowScaleFactorChanged {
+                        window: entity,
+                        scale_factor: scale_factor as f64,
+                    });
+                }
+                // The constraints are given to SDL in window units
+                apply_resize_constraints(sdl_window, &window);
+            }
+            if rescaled || matches!(win_event, SdlWindowEvent::SizeChanged(_, _)) {
+                // SDL reports the size in window units, the drawable size is in pixels
+                let (width, height) = sdl_window.drawable_size();
+                window.resolution.set_physical_resolution(width, height);
+                window_resized.write(bevy_window::WindowResized {
+                    window: entity,
+                    width: window.width(),
+                    height: window.height(),
+                });
+            }
         }
         SdlWindowEvent::Moved(x, y) => {
+            let position = bevy_math::IVec2::new(x, y);
+            // The cached window is updated below so this won't move the window again
+            window.position.set(position);
             window_moved.write(bevy_window::WindowMoved {
                 window: entity,
-                position: bevy_math::IVec2::new(x, y),
+                position,
             });
         }
         SdlWindowEvent::Shown => {
@@ -61,10 +109,19 @@ pub fn handle_sdl_window_event(
             window.visible = false;
         }
         SdlWindowEvent::Maximized => {
-            window.set_maximized(true);
+            state.set_if_neq(Sdl2WindowState {
+                minimized: false,
+                maximized: true,
+            });
         }
         SdlWindowEvent::Minimized => {
-            window.set_minimized(true);
+            state.set_if_neq(Sdl2WindowState {
+                minimized: true,
+                ..*state
+            });
+        }
+        SdlWindowEvent::Restored => {
+            state.set_if_neq(Sdl2WindowState::default());
         }
         SdlWindowEvent::Enter => {
             cursor_entered.write(bevy_window::CursorEntered { window: entity });
@@ -75,6 +132,10 @@ pub fn handle_sdl_window_event(
         }
         SdlWindowEvent::FocusGained => {
             window.focused = true;
+            if let Some(cursor_options) = cursor_options {
+                apply_focused_cursor_options(cursor_options);
+            }
+            start_text_input(sdl_window, &window);
             window_focused.write(bevy_window::WindowFocused {
                 window: entity,
                 focused: true,
@@ -82,6 +143,8 @@ pub fn handle_sdl_window_event(
         }
         SdlWindowEvent::FocusLost => {
             window.focused = false;
+            // The window that gets focus next starts text input again if it needs it
+            stop_text_input();
             window_focused.write(bevy_window::WindowFocused {
                 window: entity,
                 focused: false,
@@ -95,12 +158,25 @@ pub fn handle_sdl_window_event(
         }
     }
 
-    let (window, mut cached_window) = query.get_mut(entity).expect("failed to get Window");
+    let occluded = is_occluded(&window, &state);
+    if occluded != was_occluded {
+        window_occluded.write(bevy_window::WindowOccluded {
+            window: entity,
+            occluded,
+        });
+    }
+
+    let (window, mut cached_window, _, _) = query.get_mut(entity).expect("failed to get Window");
     if window.is_changed() {
         *cached_window = CachedWindow(window.clone());
     }
 }
 
+/// Whether nothing of the window can be seen, SDL doesn't report windows covered by other windows.
+pub(crate) fn is_occluded(window: &bevy_window::Window, state: &Sdl2WindowState) -> bool {
+    !window.visible || state.minimized
+}
+
 pub fn forward_bevy_window_events(world: &mut World, events: Vec<bevy_window::WindowEvent>) {
     use bevy_window::WindowEvent as BevyWindowEvent;
     for bevy_window_event in events.iter() {
@@ -196,3 +272,29 @@ pub fn forward_bevy_window_events(world: &mut World, events: Vec<bevy_window::Wi
             .write_batch(events);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_or_minimized_windows_are_occluded() {
+        let visible = bevy_window::Window::default();
+        let hidden = bevy_window::Window {
+            visible: false,
+            ..Default::default()
+        };
+        let minimized = Sdl2WindowState {
+            minimized: true,
+            maximized: false,
+        };
+        let maximized = Sdl2WindowState {
+            minimized: false,
+            maximized: true,
+        };
+        assert!(!is_occluded(&visible, &Sdl2WindowState::default()));
+        assert!(!is_occluded(&visible, &maximized));
+        assert!(is_occluded(&visible, &minimized));
+        assert!(is_occluded(&hidden, &Sdl2WindowState::default()));
+    }
+}
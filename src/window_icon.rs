@@ -0,0 +1,150 @@
+use bevy_asset::{AssetEvent, AssetServer, Assets, Handle, LoadState};
+use bevy_ecs::{
+    change_detection::DetectChanges,
+    component::Component,
+    entity::{Entity, EntityHashSet},
+    message::MessageReader,
+    system::{Local, NonSendMarker, Query, Res},
+    world::Ref,
+};
+use bevy_image::Image;
+use sdl2::{pixels::PixelFormatEnum, surface::Surface};
+
+use crate::{CachedWindow, SDL_WINDOWS};
+
+/// The icon of a window, shown in its title bar and the taskbar.
+///
+/// Add this component to a [`Window`](bevy_window::Window) entity. The icon is applied when the
+/// window is created and whenever this component or its image changes.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub enum Sdl2WindowIcon {
+    /// RGBA pixels with 8 bits per channel, row by row starting from the top left corner.
+    Rgba {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    /// An image asset, applied once it's loaded. A warning is logged if it fails to load.
+    Image(Handle<Image>),
+}
+
+/// RGBA pixels of an icon, ready to be handed to SDL.
+struct IconPixels {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Sdl2WindowIcon {
+    /// Returns the pixels of the icon, or `None` if its image isn't loaded yet.
+    fn pixels(&self, images: Option<&Assets<Image>>) -> Result<Option<IconPixels>, String> {
+        let icon = match self {
+            Sdl2WindowIcon::Rgba {
+                width,
+                height,
+                pixels,
+            } => IconPixels {
+                width: *width,
+                height: *height,
+                pixels: pixels.clone(),
+            },
+            Sdl2WindowIcon::Image(handle) => {
+                let Some(image) = images.and_then(|images| images.get(handle)) else {
+                    return Ok(None);
+                };
+                let rgba = image
+                    .clone()
+                    .try_into_dynamic()
+                    .map_err(|err| err.to_string())?
+                    .into_rgba8();
+                IconPixels {
+                    width: rgba.width(),
+                    height: rgba.height(),
+                    pixels: rgba.into_raw(),
+                }
+            }
+        };
+        let expected_len = icon.width as usize * icon.height as usize * 4;
+        if icon.pixels.len() != expected_len {
+            return Err(format!(
+                "expected {expected_len} bytes for a {}x{} RGBA icon, got {}",
+                icon.width,
+                icon.height,
+                icon.pixels.len()
+            ));
+        }
+        Ok(Some(icon))
+    }
+}
+
+/// Applies the [`Sdl2WindowIcon`] of each window to its SDL window.
+pub(crate) fn update_window_icons(
+    icons: Query<(Entity, Ref<Sdl2WindowIcon>, Ref<CachedWindow>)>,
+    images: Option<Res<Assets<Image>>>,
+    asset_server: Option<Res<AssetServer>>,
+    image_events: Option<MessageReader<AssetEvent<Image>>>,
+    mut pending: Local<EntityHashSet>,
+    _non_send_marker: NonSendMarker,
+) {
+    // Images can be hot reloaded
+    if let Some(mut image_events) = image_events {
+        for event in image_events.read() {
+            let AssetEvent::Modified { id } = event else {
+                continue;
+            };
+            pending.extend(icons.iter().filter_map(|(entity, icon, _)| match &*icon {
+                Sdl2WindowIcon::Image(handle) if handle.id() == *id => Some(entity),
+                _ => None,
+            }));
+        }
+    }
+    // The SDL window exists once the cached window is added
+    pending.extend(
+        icons
+            .iter()
+            .filter(|(_, icon, cache)| icon.is_changed() || cache.is_added())
+            .map(|(entity, _, _)| entity),
+    );
+    if pending.is_empty() {
+        return;
+    }
+
+    SDL_WINDOWS.with_borrow_mut(|windows| {
+        pending.retain(|&entity| {
+            let (Ok((_, icon, _)), Some(sdl_window)) =
+                (icons.get(entity), windows.get_window_mut(entity))
+            else {
+                return false;
+            };
+            let mut icon = match icon.pixels(images.as_deref()) {
+                Ok(Some(icon)) => icon,
+                Ok(None) => {
+                    if let Sdl2WindowIcon::Image(handle) = &*icon
+                        && let Some(asset_server) = &asset_server
+                        && let Some(LoadState::Failed(err)) = asset_server.get_load_state(handle)
+                    {
+                        bevy_log::warn!("Failed to load window icon for {entity}: {err}");
+                        return false;
+                    }
+                    // Try again next frame
+                    return true;
+                }
+                Err(err) => {
+                    bevy_log::error!("Invalid window icon for {entity}: {err}");
+                    return false;
+                }
+            };
+            match Surface::from_data(
+                &mut icon.pixels,
+                icon.width,
+                icon.height,
+                icon.width * 4,
+                PixelFormatEnum::RGBA32,
+            ) {
+                Ok(surface) => sdl_window.set_icon(surface),
+                Err(err) => bevy_log::error!("Failed to set window icon for {entity}: {err}"),
+            }
+            false
+        });
+    });
+}
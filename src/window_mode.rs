@@ -0,0 +1,188 @@
+use bevy_window::{MonitorSelection, VideoMode, VideoModeSelection, WindowMode};
+use sdl2::{
+    VideoSubsystem,
+    pixels::PixelFormatEnum,
+    video::{DisplayMode, FullscreenType, WindowPos},
+};
+
+use crate::converters::convert_sdl_display_mode;
+
+/// Returns the index of the SDL display matching the [`MonitorSelection`].
+///
+/// `current_display` is the display the window is on, it's `None` if the window doesn't exist yet.
+/// In that case [`MonitorSelection::Current`] falls back to the primary display.
+pub(crate) fn select_display(
+    video_subsystem: &VideoSubsystem,
+    current_display: Option<i32>,
+    selection: &MonitorSelection,
+) -> Option<i32> {
+    let display = match selection {
+        MonitorSelection::Current => current_display.unwrap_or(0),
+        // SDL always reports the primary display first
+        MonitorSelection::Primary => 0,
+        MonitorSelection::Index(index) => i32::try_from(*index).ok()?,
+        // Monitor entities are resolved to indices on the bevy thread, see `resolve_monitor`
+        MonitorSelection::Entity(entity) => {
+            bevy_log::warn!("Monitor {entity} was not resolved, using the current monitor instead");
+            current_display.unwrap_or(0)
+        }
+    };
+    let num_displays = video_subsystem.num_video_displays().ok()?;
+    if display >= num_displays {
+        bevy_log::warn!("{selection:?} does not exist, only {num_displays} displays are available");
+        return None;
+    }
+    Some(display)
+}
+
+/// Returns the SDL display mode matching the [`VideoModeSelection`] on the given display.
+///
+/// Falls back to the closest display mode supported by the display if there's no exact match.
+pub(crate) fn select_display_mode(
+    video_subsystem: &VideoSubsystem,
+    display: i32,
+    selection: &VideoModeSelection,
+) -> Option<DisplayMode> {
+    match selection {
+        VideoModeSelection::Current => video_subsystem.current_display_mode(display).ok(),
+        VideoModeSelection::Specific(video_mode) => {
+            let num_modes = video_subsystem.num_display_modes(display).ok()?;
+            let display_modes = (0..num_modes)
+                .filter_map(|mode_index| video_subsystem.display_mode(display, mode_index).ok());
+            matching_display_mode(display_modes, video_mode).or_else(|| {
+                video_subsystem
+                    .closest_display_mode(display, &display_mode_target(video_mode))
+                    .ok()
+            })
+        }
+    }
+}
+
+/// Returns the display mode that is exactly the given [`VideoMode`].
+fn matching_display_mode(
+    display_modes: impl IntoIterator<Item = DisplayMode>,
+    video_mode: &VideoMode,
+) -> Option<DisplayMode> {
+    display_modes
+        .into_iter()
+        .find(|display_mode| convert_sdl_display_mode(display_mode) == *video_mode)
+}
+
+/// Returns the display mode SDL should look for when no display mode is exactly the given
+/// [`VideoMode`], any pixel format will do.
+fn display_mode_target(video_mode: &VideoMode) -> DisplayMode {
+    DisplayMode::new(
+        PixelFormatEnum::Unknown,
+        video_mode.physical_size.x as i32,
+        video_mode.physical_size.y as i32,
+        (video_mode.refresh_rate_millihertz / 1000) as i32,
+    )
+}
+
+/// Returns the position that centers a window of the given size on a display.
+pub(crate) fn display_center(
+    video_subsystem: &VideoSubsystem,
+    display: i32,
+    width: u32,
+    height: u32,
+) -> Option<(i32, i32)> {
+    let bounds = video_subsystem.display_bounds(display).ok()?;
+    Some((
+        bounds.x() + (bounds.width() as i32 - width as i32) / 2,
+        bounds.y() + (bounds.height() as i32 - height as i32) / 2,
+    ))
+}
+
+/// Applies a [`WindowMode`] to an existing SDL window.
+pub(crate) fn apply_window_mode(sdl_window: &mut sdl2::video::Window, mode: &WindowMode) {
+    let current_display = sdl_window.display_index().ok();
+    let result = match mode {
+        WindowMode::Windowed => sdl_window.set_fullscreen(FullscreenType::Off),
+        WindowMode::BorderlessFullscreen(monitor) => {
+            if let Some(display) = select_display(sdl_window.subsystem(), current_display, monitor)
+            {
+                move_to_display(sdl_window, display);
+            }
+            sdl_window.set_fullscreen(FullscreenType::Desktop)
+        }
+        WindowMode::Fullscreen(monitor, video_mode) => {
+            let display = select_display(sdl_window.subsystem(), current_display, monitor);
+            if let Some(display) = display {
+                move_to_display(sdl_window, display);
+            }
+            let display_mode = display.and_then(|display| {
+                select_display_mode(sdl_window.subsystem(), display, video_mode)
+            });
+            // The display mode needs to be set before going fullscreen, otherwise SDL uses the
+            // window size
+            sdl_window
+                .set_display_mode(display_mode)
+                .and_then(|_| sdl_window.set_fullscreen(FullscreenType::True))
+        }
+    };
+    if let Err(err) = result {
+        bevy_log::error!("Failed to set window mode to {mode:?}: {err}");
+    }
+}
+
+/// Moves a window to the center of another display.
+fn move_to_display(sdl_window: &mut sdl2::video::Window, display: i32) {
+    if sdl_window.display_index().ok() == Some(display) {
+        return;
+    }
+    // SDL keeps fullscreen windows on their display so we need to leave fullscreen first
+    if sdl_window.fullscreen_state() != FullscreenType::Off
+        && let Err(err) = sdl_window.set_fullscreen(FullscreenType::Off)
+    {
+        bevy_log::error!("Failed to leave fullscreen: {err}");
+    }
+    let (width, height) = sdl_window.size();
+    if let Some((x, y)) = display_center(sdl_window.subsystem(), display, width, height) {
+        sdl_window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_math::UVec2;
+
+    use super::*;
+
+    fn display_mode(w: i32, h: i32, refresh_rate: i32) -> DisplayMode {
+        DisplayMode::new(PixelFormatEnum::Unknown, w, h, refresh_rate)
+    }
+
+    fn video_mode(display_mode: &DisplayMode) -> VideoMode {
+        convert_sdl_display_mode(display_mode)
+    }
+
+    #[test]
+    fn matches_size_and_refresh_rate() {
+        let display_modes = [
+            display_mode(1920, 1080, 144),
+            display_mode(1920, 1080, 60),
+            display_mode(1280, 720, 60),
+        ];
+        let wanted = video_mode(&display_mode(1920, 1080, 60));
+        let found = matching_display_mode(display_modes, &wanted).unwrap();
+        assert_eq!((found.w, found.h, found.refresh_rate), (1920, 1080, 60));
+    }
+
+    #[test]
+    fn no_match_for_unsupported_modes() {
+        let display_modes = [display_mode(1920, 1080, 60)];
+        let wanted = video_mode(&display_mode(1920, 1080, 75));
+        assert!(matching_display_mode(display_modes, &wanted).is_none());
+    }
+
+    #[test]
+    fn closest_mode_target_is_in_hertz() {
+        let target = display_mode_target(&VideoMode {
+            physical_size: UVec2::new(2560, 1440),
+            bit_depth: 32,
+            refresh_rate_millihertz: 59_940,
+        });
+        assert_eq!(target.format, PixelFormatEnum::Unknown);
+        assert_eq!((target.w, target.h, target.refresh_rate), (2560, 1440, 59));
+    }
+}
@@ -0,0 +1,23 @@
+use bevy_ecs::component::Component;
+
+/// State of a window that SDL tracks but [`Window`](bevy_window::Window) doesn't expose.
+///
+/// This is added to every window created by this backend and kept in sync with SDL. To change it,
+/// use [`Window::set_minimized`](bevy_window::Window::set_minimized) and
+/// [`Window::set_maximized`](bevy_window::Window::set_maximized).
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sdl2WindowState {
+    /// Whether the window is minimized.
+    pub minimized: bool,
+    /// Whether the window is maximized.
+    pub maximized: bool,
+}
+
+impl Sdl2WindowState {
+    pub(crate) fn from_sdl_window(sdl_window: &sdl2::video::Window) -> Self {
+        Sdl2WindowState {
+            minimized: sdl_window.is_minimized(),
+            maximized: sdl_window.is_maximized(),
+        }
+    }
+}